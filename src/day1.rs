@@ -1,45 +1,302 @@
-use anyhow::{Context, Result};
+use std::{fmt, str::FromStr};
 
-use crate::filemanip::read_lines;
+use aho_corasick::{AhoCorasick, Match};
+use anyhow::{Context, Error, Result};
+use rand::{seq::SliceRandom, Rng};
+use serde::Serialize;
 
-const WORDS_TO_VALUE: &[&str] = &[
+use crate::filemanip::{at, read_numbered_lines, ReadOptions};
+
+const WORDS_EN: &[&str] = &[
     "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
 ];
 
-fn to_values(input: &str, include_words: bool) -> Vec<u64> {
-    let mut values = vec![];
-    for i in 0..input.len() {
-        if let Some(value) = input.chars().nth(i).unwrap().to_digit(10) {
-            values.push(value as u64);
-        } else if include_words {
-            let splice = &input[i..];
-            let value = WORDS_TO_VALUE
-                .iter()
-                .enumerate()
-                .filter_map(|(i, word)| splice.starts_with(word).then_some((i + 1) as u64))
-                .next();
-            if let Some(value) = value {
-                values.push(value);
+const WORDS_DE: &[&str] = &[
+    "eins", "zwei", "drei", "vier", "fünf", "sechs", "sieben", "acht", "neun",
+];
+
+const WORDS_FR: &[&str] = &[
+    "un", "deux", "trois", "quatre", "cinq", "six", "sept", "huit", "neuf",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    English,
+    German,
+    French,
+}
+
+impl Locale {
+    fn words(&self) -> &'static [&'static str] {
+        match self {
+            Locale::English => WORDS_EN,
+            Locale::German => WORDS_DE,
+            Locale::French => WORDS_FR,
+        }
+    }
+
+    fn zero(&self) -> &'static str {
+        match self {
+            Locale::English => "zero",
+            Locale::German => "null",
+            Locale::French => "zéro",
+        }
+    }
+}
+
+impl FromStr for Locale {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "en" => Ok(Locale::English),
+            "de" => Ok(Locale::German),
+            "fr" => Ok(Locale::French),
+            _ => Err(Error::msg(format!("Unknown locale {:?}", s))),
+        }
+    }
+}
+
+/// The spelled-out words that count as digits, in addition to the digits themselves.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Vocabulary {
+    words: Vec<(String, u64)>,
+}
+
+impl Vocabulary {
+    pub fn from_locale(locale: Locale, include_zero: bool) -> Self {
+        let mut words = locale
+            .words()
+            .iter()
+            .enumerate()
+            .map(|(i, word)| (word.to_string(), (i + 1) as u64))
+            .collect::<Vec<_>>();
+        if include_zero {
+            words.push((locale.zero().to_owned(), 0));
+        }
+        Vocabulary { words }
+    }
+
+    /// Reads a word list with one `<word> <value>` pair per line. Empty lines and lines
+    /// starting with `#` are ignored.
+    pub fn from_file(file: &std::path::Path) -> Result<Self> {
+        let mut words = vec![];
+        for line in read_numbered_lines(file, ReadOptions::default())? {
+            let (line_number, line) = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (word, value) = line
+                .split_once(char::is_whitespace)
+                .and_then(|(word, value)| Some((word, value.trim().parse::<u64>().ok()?)))
+                .ok_or(Error::msg(format!("Malformed word list entry {:?}", line)))
+                .context(at(file, line_number))?;
+            if value > 9 {
+                return Err(
+                    Error::msg(format!("{:?} does not name a single digit", word))
+                        .context(at(file, line_number)),
+                );
+            }
+            words.push((word.to_owned(), value));
+        }
+        Ok(Vocabulary { words })
+    }
+
+    pub fn extend(&mut self, other: Vocabulary) {
+        self.words.extend(other.words);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenKind {
+    Digit,
+    Word,
+}
+
+/// A digit or spelled-out digit found in a line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Token {
+    pub text: String,
+    pub value: u64,
+    pub kind: TokenKind,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            TokenKind::Digit => write!(f, "{} (digit)", self.text),
+            TokenKind::Word => write!(f, "{:?} (word, {})", self.text, self.value),
+        }
+    }
+}
+
+/// Finds all digits and vocabulary words in a line in a single pass, including
+/// overlapping ones such as `eightwo`.
+struct Matcher {
+    automaton: AhoCorasick,
+    values: Vec<u64>,
+}
+
+impl Matcher {
+    const DIGIT_PATTERNS: usize = 10;
+
+    fn new(vocabulary: &Vocabulary) -> Result<Self> {
+        let mut patterns = (0..=9).map(|d| d.to_string()).collect::<Vec<_>>();
+        let mut values = (0..=9).collect::<Vec<u64>>();
+        for (word, value) in &vocabulary.words {
+            patterns.push(word.clone());
+            values.push(*value);
+        }
+        let automaton = AhoCorasick::new(&patterns).context("Failed to build word matcher")?;
+        Ok(Matcher { automaton, values })
+    }
+
+    /// Returns the tokens starting first and last in the line.
+    fn first_and_last(&self, line: &str) -> Option<(Token, Token)> {
+        let mut first = None;
+        let mut last = None;
+        for m in self.automaton.find_overlapping_iter(line) {
+            if first.is_none_or(|first: Match| m.start() < first.start()) {
+                first = Some(m);
+            }
+            if last.is_none_or(|last: Match| m.start() >= last.start()) {
+                last = Some(m);
             }
         }
+        let to_token = |m: Match| {
+            let pattern = m.pattern().as_usize();
+            Token {
+                text: line[m.range()].to_owned(),
+                value: self.values[pattern],
+                kind: if pattern < Self::DIGIT_PATTERNS {
+                    TokenKind::Digit
+                } else {
+                    TokenKind::Word
+                },
+            }
+        };
+        Some((to_token(first?), to_token(last?)))
     }
-    values
 }
 
-pub fn calculate_calibration_sum(file: &std::path::Path, include_words: bool) -> Result<u64> {
-    let mut sum = 0u64;
-    for line in read_lines(file)
-        .context(format!("Failed to read file {:#?}", file))?
-        .flatten()
-    {
-        let nums = to_values(&line, include_words);
-        let first = nums
-            .first()
-            .context(format!("No numbers in line {:#}", &line))?;
-        let last = nums
-            .last()
-            .expect("We just checked that nums contains values.");
-        sum += (first * 10 + last) as u64;
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LineCalibration {
+    pub line_number: usize,
+    pub line: String,
+    pub first: Token,
+    pub last: Token,
+}
+
+impl LineCalibration {
+    pub fn value(&self) -> u64 {
+        self.first.value * 10 + self.last.value
+    }
+}
+
+/// The calibration of every line of a document, including the lines without any value.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CalibrationReport {
+    pub lines: Vec<LineCalibration>,
+    pub missing: Vec<(usize, String)>,
+}
+
+impl CalibrationReport {
+    pub fn sum(&self) -> u64 {
+        self.lines.iter().map(LineCalibration::value).sum()
+    }
+}
+
+impl fmt::Display for CalibrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(
+                f,
+                "{:>5}: {:<40} first {}, last {} => {}",
+                line.line_number,
+                line.line,
+                line.first,
+                line.last,
+                line.value()
+            )?;
+        }
+        if !self.missing.is_empty() {
+            writeln!(f, "Lines without a calibration value:")?;
+            for (line_number, line) in &self.missing {
+                writeln!(f, "{:>5}: {}", line_number, line)?;
+            }
+        }
+        writeln!(
+            f,
+            "Sum of {} lines ({} skipped): {}",
+            self.lines.len(),
+            self.missing.len(),
+            self.sum()
+        )
+    }
+}
+
+pub fn calibration_report(
+    file: &std::path::Path,
+    options: ReadOptions,
+    vocabulary: &Vocabulary,
+) -> Result<CalibrationReport> {
+    let matcher = Matcher::new(vocabulary)?;
+    let mut report = CalibrationReport::default();
+    for line in read_numbered_lines(file, options)? {
+        let (line_number, line) = line?;
+        match matcher.first_and_last(&line) {
+            Some((first, last)) => report.lines.push(LineCalibration {
+                line_number,
+                line,
+                first,
+                last,
+            }),
+            None => report.missing.push((line_number, line)),
+        }
+    }
+    Ok(report)
+}
+
+pub fn calculate_calibration_sum(
+    file: &std::path::Path,
+    options: ReadOptions,
+    vocabulary: &Vocabulary,
+    skip_missing: bool,
+) -> Result<u64> {
+    let report = calibration_report(file, options, vocabulary)?;
+    if let Some((line_number, line)) = report.missing.first().filter(|_| !skip_missing) {
+        return Err(
+            Error::msg(format!("No numbers in line {:#}", line)).context(at(file, *line_number))
+        );
+    }
+    Ok(report.sum())
+}
+
+/// Generates a calibration document with `lines` lines. Every line contains at least one
+/// digit and may contain spelled-out English digits, including overlapping ones.
+pub fn generate_input(rng: &mut impl Rng, lines: usize) -> String {
+    let mut out = String::new();
+    for _ in 0..lines {
+        let mut line = String::new();
+        let mut has_digit = false;
+        for _ in 0..rng.gen_range(1..=8) {
+            match rng.gen_range(0..4) {
+                0 => {
+                    line.push(char::from_digit(rng.gen_range(1..=9), 10).unwrap());
+                    has_digit = true;
+                }
+                1 => line.push_str(WORDS_EN.choose(rng).unwrap()),
+                _ => line.extend((0..rng.gen_range(1..=5)).map(|_| rng.gen_range('a'..='z'))),
+            }
+        }
+        if !has_digit {
+            let pos = rng.gen_range(0..=line.len());
+            line.insert(pos, char::from_digit(rng.gen_range(1..=9), 10).unwrap());
+        }
+        out.push_str(&line);
+        out.push('\n');
     }
-    Ok(sum)
+    out
 }
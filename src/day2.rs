@@ -1,108 +1,432 @@
 use anyhow::{Context, Error, Result};
-use std::str::FromStr;
+use rand::{seq::SliceRandom, Rng};
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt, ops::Add, str::FromStr};
 
-use crate::filemanip::read_lines;
+use crate::filemanip::{at, read_numbered_lines, ReadOptions};
 
-#[derive(Debug, PartialEq, Eq, Default)]
-struct Cubes {
-    red: u64,
-    green: u64,
-    blue: u64,
+/// The colors every bag in the puzzle is made of. A game that never shows one of
+/// these colors has a power of zero.
+const STANDARD_COLORS: &[&str] = &["red", "green", "blue"];
+
+/// A multiset of cubes, keyed by color name.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+#[serde(transparent)]
+pub struct Cubes {
+    counts: BTreeMap<String, u64>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
-struct ParseCubesError;
+impl Cubes {
+    pub fn get(&self, color: &str) -> u64 {
+        self.counts.get(color).copied().unwrap_or(0)
+    }
+
+    pub fn insert(&mut self, color: &str, count: u64) {
+        self.counts.insert(color.to_owned(), count);
+    }
+
+    pub fn colors(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.counts
+            .iter()
+            .map(|(color, count)| (color.as_str(), *count))
+    }
+
+    /// The component-wise maximum of both multisets.
+    pub fn max(&self, other: &Cubes) -> Cubes {
+        let mut max = self.clone();
+        for (color, count) in other.colors() {
+            let entry = max.counts.entry(color.to_owned()).or_insert(0);
+            *entry = (*entry).max(count);
+        }
+        max
+    }
+
+    pub fn is_subset_of(&self, other: &Cubes) -> bool {
+        self.colors()
+            .all(|(color, count)| count <= other.get(color))
+    }
+
+    /// The product of the counts of all colors in the multiset.
+    pub fn power(&self) -> u64 {
+        self.colors().map(|(_, count)| count).product()
+    }
+}
+
+impl Add for &Cubes {
+    type Output = Cubes;
+
+    fn add(self, other: &Cubes) -> Cubes {
+        let mut sum = self.clone();
+        for (color, count) in other.colors() {
+            *sum.counts.entry(color.to_owned()).or_insert(0) += count;
+        }
+        sum
+    }
+}
+
+impl fmt::Display for Cubes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, (color, count)) in self.colors().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} {}", count, color)?;
+        }
+        Ok(())
+    }
+}
 
 impl FromStr for Cubes {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Cubes::parse(s, s, ParseMode::Strict)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    /// Reject duplicate colors within a round, empty rounds and non-sequential game ids.
+    #[default]
+    Strict,
+    /// Sum duplicate colors, skip empty rounds and accept any game id.
+    Lenient,
+}
+
+/// The 1-based column at which `part`, a subslice of `line`, starts.
+fn column(line: &str, part: &str) -> usize {
+    part.as_ptr() as usize - line.as_ptr() as usize + 1
+}
+
+impl Cubes {
+    /// Parses the cube list `s`, which is a subslice of `line`. Error positions are
+    /// reported relative to `line`.
+    fn parse(line: &str, s: &str, mode: ParseMode) -> Result<Self> {
         let mut cubes = Cubes::default();
         for entry in s.split(',') {
-            let (number, kind) = entry
-                .trim()
-                .split_once(' ')
-                .and_then(|(number, kind)| Some((number.parse::<u64>().ok()?, kind.trim())))
-                .ok_or(Error::msg("Failed to parse cube list"))?;
-            match kind {
-                "red" => cubes.red = number,
-                "green" => cubes.green = number,
-                "blue" => cubes.blue = number,
-                _ => return Err(Error::msg("Invalid color specified")),
+            let mut parts = entry.split_whitespace();
+            let (number, color) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(number), Some(color), None) => (number, color),
+                _ => {
+                    return Err(Error::msg(format!(
+                        "Failed to parse cube list entry {:?} at column {}",
+                        entry.trim(),
+                        column(line, entry)
+                    )))
+                }
+            };
+            let count = number.parse::<u64>().context(format!(
+                "Invalid cube count {:?} at column {}",
+                number,
+                column(line, number)
+            ))?;
+            match cubes.counts.get_mut(color) {
+                Some(previous) if mode == ParseMode::Lenient => {
+                    *previous = previous.checked_add(count).ok_or(Error::msg(format!(
+                        "Total count of {:?} overflows at column {}",
+                        color,
+                        column(line, number)
+                    )))?
+                }
+                Some(_) => {
+                    return Err(Error::msg(format!(
+                        "Duplicate color {:?} at column {}",
+                        color,
+                        column(line, color)
+                    )))
+                }
+                None => cubes.insert(color, count),
             }
         }
         Ok(cubes)
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-struct Game {
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Game {
     id: u64,
     rounds: Vec<Cubes>,
 }
 
+/// A color in a round that showed more cubes than the bag holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub round: usize,
+    pub color: String,
+    pub count: u64,
+    pub limit: u64,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "round {} shows {} {} (limit {})",
+            self.round, self.count, self.color, self.limit
+        )
+    }
+}
+
+impl Game {
+    /// The smallest bag this game could have been played with.
+    pub fn minimum_bag(&self) -> Cubes {
+        self.rounds
+            .iter()
+            .fold(Cubes::default(), |max, round| max.max(round))
+    }
+
+    /// Every round and color that exceeds the contents of `bag`. Rounds are numbered from 1.
+    pub fn violations(&self, bag: &Cubes) -> Vec<Violation> {
+        self.rounds
+            .iter()
+            .enumerate()
+            .flat_map(|(round_idx, round)| {
+                round
+                    .colors()
+                    .filter(|(color, count)| *count > bag.get(color))
+                    .map(move |(color, count)| Violation {
+                        round: round_idx + 1,
+                        color: color.to_owned(),
+                        count,
+                        limit: bag.get(color),
+                    })
+            })
+            .collect()
+    }
+}
+
+impl Game {
+    /// Parses a game. If `expected_id` is given, the game must have exactly that id.
+    pub fn parse(s: &str, mode: ParseMode, expected_id: Option<u64>) -> Result<Self> {
+        let (id, rounds) = s
+            .strip_prefix("Game")
+            .filter(|rest| rest.starts_with(char::is_whitespace))
+            .and_then(|rest| rest.split_once(':'))
+            .ok_or(Error::msg("Failed to parse game structure"))?;
+        let id_str = id.trim();
+        let id = id_str.parse::<u64>().context(format!(
+            "Invalid game id {:?} at column {}",
+            id_str,
+            column(s, id_str)
+        ))?;
+        if let Some(expected_id) = expected_id.filter(|expected_id| *expected_id != id) {
+            return Err(Error::msg(format!(
+                "Game id {} at column {} is not sequential, expected {}",
+                id,
+                column(s, id_str),
+                expected_id
+            )));
+        }
+
+        let mut parsed_rounds = vec![];
+        for round in rounds.split(';') {
+            if round.trim().is_empty() {
+                match mode {
+                    ParseMode::Strict => {
+                        return Err(Error::msg(format!(
+                            "Empty round at column {}",
+                            column(s, round)
+                        )))
+                    }
+                    ParseMode::Lenient => continue,
+                }
+            }
+            parsed_rounds.push(Cubes::parse(s, round, mode)?);
+        }
+
+        Ok(Game {
+            id,
+            rounds: parsed_rounds,
+        })
+    }
+}
+
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Game {}: ", self.id)?;
+        for (idx, round) in self.rounds.iter().enumerate() {
+            if idx > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", round)?;
+        }
+        Ok(())
+    }
+}
+
 impl FromStr for Game {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (id, rounds) = s
-            .strip_prefix("Game ")
-            .and_then(|s| s.split_once(':'))
-            .and_then(|(id, rounds)| Some((id.parse::<u64>().ok()?, rounds.trim())))
-            .ok_or(Error::msg("Failed to parse game structure"))?;
+        Game::parse(s, ParseMode::Strict, None)
+    }
+}
+
+pub fn read_games(
+    file: &std::path::Path,
+    options: ReadOptions,
+    mode: ParseMode,
+) -> Result<Vec<Game>> {
+    let mut games = vec![];
+    for line in read_numbered_lines(file, options)? {
+        let (line_number, line) = line?;
+        let expected_id = (mode == ParseMode::Strict).then_some(games.len() as u64 + 1);
+        games.push(
+            Game::parse(&line, mode, expected_id)
+                .context(format!("Failed to parse game {}", at(file, line_number)))?,
+        );
+    }
+    Ok(games)
+}
 
-        let rounds = rounds
-            .split(';')
-            .map(|round| round.parse::<Cubes>())
-            .collect::<Result<Vec<Cubes>>>()?;
+pub fn calculate_valid_game_sum(
+    file: &std::path::Path,
+    options: ReadOptions,
+    bag: &Cubes,
+    mode: ParseMode,
+) -> Result<u64> {
+    Ok(read_games(file, options, mode)?
+        .iter()
+        .filter(|game| game.minimum_bag().is_subset_of(bag))
+        .map(|game| game.id)
+        .sum())
+}
 
-        Ok(Game { id, rounds })
+/// The games that are impossible with a given bag, together with the reasons why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidityReport {
+    pub invalid_games: Vec<(u64, Vec<Violation>)>,
+    pub valid_id_sum: u64,
+}
+
+impl fmt::Display for ValidityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (id, violations) in &self.invalid_games {
+            writeln!(f, "Game {}:", id)?;
+            for violation in violations {
+                writeln!(f, "    {}", violation)?;
+            }
+        }
+        writeln!(f, "{} invalid games", self.invalid_games.len())?;
+        writeln!(f, "Sum of valid game ids: {}", self.valid_id_sum)
     }
 }
 
-pub fn calculate_valid_game_sum(file: &std::path::Path) -> Result<u64> {
-    let mut sum = 0u64;
-    let max_cubes = Cubes {
-        red: 12,
-        green: 13,
-        blue: 14,
+pub fn validity_report(
+    file: &std::path::Path,
+    options: ReadOptions,
+    bag: &Cubes,
+    mode: ParseMode,
+) -> Result<ValidityReport> {
+    let mut report = ValidityReport {
+        invalid_games: vec![],
+        valid_id_sum: 0,
     };
-    for line in read_lines(file)
-        .context(format!("Failed to read file {:#?}", file))?
-        .flatten()
-    {
-        let game = line.parse::<Game>()?;
-        if game.rounds.iter().all(|cubes| {
-            cubes.red <= max_cubes.red
-                && cubes.green <= max_cubes.green
-                && cubes.blue <= max_cubes.blue
-        }) {
-            sum += game.id;
+    for game in read_games(file, options, mode)? {
+        let violations = game.violations(bag);
+        if violations.is_empty() {
+            report.valid_id_sum += game.id;
+        } else {
+            report.invalid_games.push((game.id, violations));
         }
     }
-    Ok(sum)
+    Ok(report)
 }
 
-pub fn calculate_game_power_sum(file: &std::path::Path) -> Result<u64> {
-    let mut sum: u64 = 0u64;
-    for line in read_lines(file)
-        .context(format!("Failed to read file {:#?}", file))?
-        .flatten()
-    {
-        let game = line.parse::<Game>()?;
-        let mut max = Cubes::default();
-        for round in game.rounds {
-            if round.red > max.red {
-                max.red = round.red;
-            }
-            if round.green > max.green {
-                max.green = round.green;
-            }
-            if round.blue > max.blue {
-                max.blue = round.blue;
+pub fn calculate_game_power_sum(
+    file: &std::path::Path,
+    options: ReadOptions,
+    mode: ParseMode,
+) -> Result<u64> {
+    let mut empty_bag = Cubes::default();
+    for color in STANDARD_COLORS {
+        empty_bag.insert(color, 0);
+    }
+    Ok(read_games(file, options, mode)?
+        .iter()
+        .map(|game| game.minimum_bag().max(&empty_bag).power())
+        .sum())
+}
+
+/// Generates `games` games with sequential ids, each with one to six rounds that show
+/// between one and twenty cubes of each of a random selection of the standard colors.
+pub fn generate_input(rng: &mut impl Rng, games: usize) -> String {
+    let mut out = String::new();
+    for id in 1..=games {
+        let rounds = (0..rng.gen_range(1..=6))
+            .map(|_| {
+                let mut colors = STANDARD_COLORS.to_vec();
+                colors.shuffle(rng);
+                colors.truncate(rng.gen_range(1..=colors.len()));
+                let mut round = Cubes::default();
+                for color in colors {
+                    round.insert(color, rng.gen_range(1..=20));
+                }
+                round
+            })
+            .collect();
+        let game = Game {
+            id: id as u64,
+            rounds,
+        };
+        out.push_str(&format!("{}\n", game));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::{collection, prelude::*};
+
+    fn cubes() -> impl Strategy<Value = Cubes> {
+        collection::btree_map("[a-z]{1,8}", any::<u64>(), 1..5).prop_map(|counts| Cubes { counts })
+    }
+
+    fn game() -> impl Strategy<Value = Game> {
+        (any::<u64>(), collection::vec(cubes(), 1..6)).prop_map(|(id, rounds)| Game { id, rounds })
+    }
+
+    proptest! {
+        #[test]
+        fn cubes_round_trip(cubes in cubes()) {
+            prop_assert_eq!(cubes.to_string().parse::<Cubes>().unwrap(), cubes);
+        }
+
+        #[test]
+        fn lenient_cubes_sum_duplicates(a in cubes(), b in cubes()) {
+            let list = format!("{}, {}", a, b);
+            let parsed = Cubes::parse(&list, &list, ParseMode::Lenient);
+            if a.colors().any(|(color, count)| count.checked_add(b.get(color)).is_none()) {
+                prop_assert!(parsed.is_err());
+            } else {
+                prop_assert_eq!(parsed.unwrap(), &a + &b);
             }
         }
-        sum += max.red * max.green * max.blue;
+
+        #[test]
+        fn cubes_are_subset_of_max(a in cubes(), b in cubes()) {
+            let max = a.max(&b);
+            prop_assert!(a.is_subset_of(&max));
+            prop_assert!(b.is_subset_of(&max));
+        }
+
+        #[test]
+        fn game_round_trip(game in game()) {
+            prop_assert_eq!(game.to_string().parse::<Game>().unwrap(), game);
+        }
+
+        #[test]
+        fn game_rejects_unexpected_id(game in game(), expected_id in any::<u64>()) {
+            prop_assume!(expected_id != game.id);
+            prop_assert!(Game::parse(&game.to_string(), ParseMode::Strict, Some(expected_id)).is_err());
+        }
+
+        #[test]
+        fn game_fits_its_minimum_bag(game in game()) {
+            let bag = game.minimum_bag();
+            prop_assert!(game.violations(&bag).is_empty());
+            prop_assert!(game.rounds.iter().all(|round| round.is_subset_of(&bag)));
+        }
     }
-    Ok(sum)
 }
@@ -1,112 +1,344 @@
-use std::{cmp, collections::HashMap};
-
-use anyhow::Result;
-
-use crate::filemanip::read_lines;
-
-pub fn calculate_schematic_sum(file: &std::path::Path) -> Result<u64> {
-    let mut sum = 0u64;
-    let lines = read_lines(file)?.collect::<Result<Vec<_>, _>>()?;
-    for (row_idx, row) in lines.iter().enumerate() {
-        for (col_idx, c) in row.chars().enumerate() {
-            // Check if a number STARTS at col_idx
-            if c.is_ascii_digit()
-                && (col_idx == 0
-                    || row
-                        .chars()
-                        .nth(col_idx - 1)
-                        .is_some_and(|c| !c.is_ascii_digit()))
-            {
-                let mut value = 0u64;
-                let mut view_idx = col_idx;
-                while row
-                    .chars()
-                    .nth(view_idx)
-                    .is_some_and(|c| c.is_ascii_digit())
-                {
-                    value = value * 10
-                        + row
-                            .chars()
-                            .nth(view_idx)
-                            .expect("while condition")
-                            .to_digit(10)
-                            .expect("while condition") as u64;
-                    view_idx += 1;
-                }
+use std::{
+    collections::HashMap,
+    ops::{Range, RangeInclusive},
+    str::FromStr,
+};
+
+use anyhow::{Error, Result};
+use rand::{seq::SliceRandom, Rng};
+use serde::Serialize;
+
+use crate::{
+    filemanip::{InputFile, ReadOptions},
+    grid::Grid,
+};
+
+fn is_symbol(c: char) -> bool {
+    !c.is_ascii_digit() && c != '.'
+}
+
+/// A number in the schematic. `adjacent_symbols` indexes into [`Schematic::symbols`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Number {
+    pub value: u64,
+    pub row: usize,
+    pub cols: Range<usize>,
+    pub adjacent_symbols: Vec<usize>,
+}
+
+/// A symbol in the schematic. `adjacent_numbers` indexes into [`Schematic::numbers`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Symbol {
+    pub kind: char,
+    pub row: usize,
+    pub col: usize,
+    pub adjacent_numbers: Vec<usize>,
+}
+
+/// How the numbers around a gear are combined into its ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Combine {
+    Product,
+    Sum,
+}
+
+impl FromStr for Combine {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "product" => Ok(Combine::Product),
+            "sum" => Ok(Combine::Sum),
+            _ => Err(Error::msg(format!("Unknown combining operation {:?}", s))),
+        }
+    }
+}
+
+/// Parses a neighbour count given either as `N` or as an inclusive range `MIN-MAX`.
+pub fn parse_neighbour_count(s: &str) -> Result<RangeInclusive<usize>> {
+    let (min, max) = s.split_once('-').unwrap_or((s, s));
+    let min = min.trim().parse::<usize>()?;
+    let max = max.trim().parse::<usize>()?;
+    if min > max {
+        return Err(Error::msg(format!("Empty neighbour count range {}", s)));
+    }
+    Ok(min..=max)
+}
+
+/// Which symbols count as gears and how their ratio is computed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GearRule {
+    pub symbols: Vec<char>,
+    pub neighbours: RangeInclusive<usize>,
+    pub combine: Combine,
+}
 
-                let mut symbol_found = false;
-                for row_offset in -1i32..=1 {
-                    if let Some(comp_row) = lines.get((row_idx as i32 + row_offset) as usize) {
-                        for col in cmp::max(col_idx, 1) - 1..=view_idx {
-                            if let Some(comp_c) = comp_row.chars().nth(col) {
-                                if !comp_c.is_ascii_digit() && comp_c != '.' {
-                                    symbol_found = true;
-                                }
-                            }
+impl Default for GearRule {
+    /// A `*` next to exactly two numbers, whose ratio is their product.
+    fn default() -> Self {
+        GearRule {
+            symbols: vec!['*'],
+            neighbours: 2..=2,
+            combine: Combine::Product,
+        }
+    }
+}
+
+/// An index of all numbers and symbols in an engine schematic and which of them touch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Schematic {
+    #[serde(skip)]
+    grid: Grid<char>,
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+}
+
+impl Schematic {
+    pub fn new(grid: Grid<char>) -> Self {
+        let mut symbols = vec![];
+        let mut symbol_at = HashMap::new();
+        for ((row, col), c) in grid.iter().filter(|(_, c)| is_symbol(**c)) {
+            symbol_at.insert((row, col), symbols.len());
+            symbols.push(Symbol {
+                kind: *c,
+                row,
+                col,
+                adjacent_numbers: vec![],
+            });
+        }
+
+        let mut numbers = vec![];
+        for (row_idx, row) in grid.rows().enumerate() {
+            let mut current: Option<Number> = None;
+            for (col_idx, c) in row.iter().enumerate() {
+                let Some(digit) = c.to_digit(10) else {
+                    numbers.extend(current.take());
+                    continue;
+                };
+                let number = current.get_or_insert(Number {
+                    value: 0,
+                    row: row_idx,
+                    cols: col_idx..col_idx,
+                    adjacent_symbols: vec![],
+                });
+                number.value = number.value * 10 + digit as u64;
+                number.cols.end = col_idx + 1;
+                for pos in grid.neighbours8(row_idx, col_idx) {
+                    if let Some(symbol) = symbol_at.get(&pos) {
+                        if !number.adjacent_symbols.contains(symbol) {
+                            number.adjacent_symbols.push(*symbol);
                         }
                     }
                 }
-                if symbol_found {
-                    sum += value;
-                }
             }
+            numbers.extend(current);
+        }
+
+        for (number_idx, number) in numbers.iter().enumerate() {
+            for symbol in &number.adjacent_symbols {
+                symbols[*symbol].adjacent_numbers.push(number_idx);
+            }
+        }
+
+        Schematic {
+            grid,
+            numbers,
+            symbols,
         }
     }
-    Ok(sum)
-}
-
-pub fn calculate_gear_ratio_sum(file: &std::path::Path) -> Result<u64> {
-    let mut star_adjacent = HashMap::new();
-    let lines = read_lines(file)?.collect::<Result<Vec<_>, _>>()?;
-    for (row_idx, row) in lines.iter().enumerate() {
-        for (col_idx, c) in row.chars().enumerate() {
-            // Check if a number STARTS at col_idx
-            if c.is_ascii_digit()
-                && (col_idx == 0
-                    || row
-                        .chars()
-                        .nth(col_idx - 1)
-                        .is_some_and(|c| !c.is_ascii_digit()))
-            {
-                let mut value = 0u64;
-                let mut view_idx = col_idx;
-                while row
-                    .chars()
-                    .nth(view_idx)
-                    .is_some_and(|c| c.is_ascii_digit())
-                {
-                    value = value * 10
-                        + row
-                            .chars()
-                            .nth(view_idx)
-                            .expect("while condition")
-                            .to_digit(10)
-                            .expect("while condition") as u64;
-                    view_idx += 1;
-                }
 
-                for row_offset in -1i32..=1 {
-                    let row = (row_idx as i32 + row_offset) as usize;
-                    if let Some(comp_row) = lines.get(row) {
-                        for col in cmp::max(col_idx, 1) - 1..=view_idx {
-                            if let Some(comp_c) = comp_row.chars().nth(col) {
-                                if comp_c == '*' {
-                                    let entry = star_adjacent
-                                        .entry((row, col))
-                                        .or_insert_with(Vec::<u64>::new);
-                                    entry.push(value);
-                                }
-                            }
-                        }
-                    }
-                }
+    pub fn from_file(file: &std::path::Path, options: ReadOptions) -> Result<Self> {
+        let input = InputFile::open(file, options)?;
+        let lines = input.lines().collect::<Vec<_>>();
+        Ok(Schematic::new(Grid::from_lines(&lines, file)?))
+    }
+
+    pub fn grid(&self) -> &Grid<char> {
+        &self.grid
+    }
+
+    pub fn numbers(&self) -> &[Number] {
+        &self.numbers
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    pub fn adjacent_symbols<'a>(&'a self, number: &'a Number) -> impl Iterator<Item = &'a Symbol> {
+        number
+            .adjacent_symbols
+            .iter()
+            .map(|symbol| &self.symbols[*symbol])
+    }
+
+    pub fn adjacent_numbers<'a>(&'a self, symbol: &'a Symbol) -> impl Iterator<Item = &'a Number> {
+        symbol
+            .adjacent_numbers
+            .iter()
+            .map(|number| &self.numbers[*number])
+    }
+
+    /// The numbers adjacent to at least one symbol.
+    pub fn part_numbers(&self) -> impl Iterator<Item = &Number> {
+        self.numbers()
+            .iter()
+            .filter(|number| !number.adjacent_symbols.is_empty())
+    }
+
+    /// The symbols that are gears according to `rule`.
+    pub fn gears<'a>(&'a self, rule: &'a GearRule) -> impl Iterator<Item = &'a Symbol> {
+        self.symbols().iter().filter(|symbol| {
+            rule.symbols.contains(&symbol.kind)
+                && rule.neighbours.contains(&symbol.adjacent_numbers.len())
+        })
+    }
+
+    pub fn gear_ratio(&self, gear: &Symbol, rule: &GearRule) -> u64 {
+        let values = self.adjacent_numbers(gear).map(|number| number.value);
+        match rule.combine {
+            Combine::Product => values.product(),
+            Combine::Sum => values.sum(),
+        }
+    }
+
+    /// The numbers adjacent to at least one symbol of the given kind.
+    pub fn numbers_touching(&self, kind: char) -> impl Iterator<Item = &Number> {
+        self.numbers.iter().filter(move |number| {
+            self.adjacent_symbols(number)
+                .any(|symbol| symbol.kind == kind)
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Highlight {
+    PartNumber,
+    OtherNumber,
+    Gear,
+}
+
+impl Highlight {
+    fn markers(&self, color: bool) -> (&'static str, &'static str) {
+        match (self, color) {
+            (Highlight::PartNumber, true) => ("\x1b[32m", "\x1b[0m"),
+            (Highlight::OtherNumber, true) => ("\x1b[31m", "\x1b[0m"),
+            (Highlight::Gear, true) => ("\x1b[1;33m", "\x1b[0m"),
+            (Highlight::PartNumber, false) => ("[", "]"),
+            (Highlight::OtherNumber, false) => ("(", ")"),
+            (Highlight::Gear, false) => ("{", "}"),
+        }
+    }
+}
+
+/// Renders the schematic with part numbers, other numbers and gears highlighted, using
+/// ANSI colors if `color` is set and brackets otherwise, followed by per-category counts.
+pub fn render_schematic(schematic: &Schematic, rule: &GearRule, color: bool) -> String {
+    // Maps the first cell of each highlighted span to its highlight and last column.
+    let mut spans = HashMap::new();
+    let mut part_count = 0;
+    for number in schematic.numbers() {
+        let highlight = if number.adjacent_symbols.is_empty() {
+            Highlight::OtherNumber
+        } else {
+            part_count += 1;
+            Highlight::PartNumber
+        };
+        spans.insert(
+            (number.row, number.cols.start),
+            (highlight, number.cols.end - 1),
+        );
+    }
+    let mut gear_count = 0;
+    for gear in schematic.gears(rule) {
+        gear_count += 1;
+        spans.insert((gear.row, gear.col), (Highlight::Gear, gear.col));
+    }
+
+    let mut out = String::new();
+    for (row_idx, row) in schematic.grid().rows().enumerate() {
+        let mut closing = None;
+        for (col_idx, c) in row.iter().enumerate() {
+            if let Some((highlight, end)) = spans.get(&(row_idx, col_idx)) {
+                let (open, close) = highlight.markers(color);
+                out.push_str(open);
+                closing = Some((close, *end));
+            }
+            out.push(*c);
+            if let Some((close, _)) = closing.filter(|(_, end)| *end == col_idx) {
+                out.push_str(close);
+                closing = None;
+            }
+        }
+        out.push('\n');
+    }
+
+    if !color {
+        out.push_str("Legend: [part number] (other number) {gear}\n");
+    }
+    out.push_str(&format!(
+        "Part numbers: {}\nOther numbers: {}\nGears: {}\n",
+        part_count,
+        schematic.numbers().len() - part_count,
+        gear_count
+    ));
+    out
+}
+
+/// Sums the part numbers, or only the numbers touching `symbol` if one is given.
+pub fn calculate_schematic_sum(
+    file: &std::path::Path,
+    options: ReadOptions,
+    symbol: Option<char>,
+) -> Result<u64> {
+    let schematic = Schematic::from_file(file, options)?;
+    Ok(match symbol {
+        Some(kind) => schematic
+            .numbers_touching(kind)
+            .map(|number| number.value)
+            .sum(),
+        None => schematic.part_numbers().map(|number| number.value).sum(),
+    })
+}
+
+pub fn calculate_gear_ratio_sum(
+    file: &std::path::Path,
+    options: ReadOptions,
+    rule: &GearRule,
+) -> Result<u64> {
+    let schematic = Schematic::from_file(file, options)?;
+    Ok(schematic
+        .gears(rule)
+        .map(|gear| schematic.gear_ratio(gear, rule))
+        .sum())
+}
+
+const GENERATED_SYMBOLS: &[char] = &['*', '#', '+', '$', '/', '=', '%', '@', '&', '-'];
+
+/// Generates a `size` by `size` schematic of numbers with up to three digits and symbols
+/// scattered between runs of `.`.
+pub fn generate_input(rng: &mut impl Rng, size: usize) -> String {
+    let mut out = String::new();
+    for _ in 0..size {
+        let mut row = String::new();
+        loop {
+            let gap = rng.gen_range(1..=6);
+            let item = if rng.gen_bool(0.6) {
+                let digits = rng.gen_range(1..=3);
+                rng.gen_range(10u64.pow(digits - 1)..10u64.pow(digits))
+                    .to_string()
+            } else {
+                GENERATED_SYMBOLS.choose(rng).unwrap().to_string()
+            };
+            if row.len() + gap + item.len() > size {
+                break;
             }
+            row.extend(std::iter::repeat_n('.', gap));
+            row.push_str(&item);
         }
+        row.extend(std::iter::repeat_n('.', size - row.len()));
+        out.push_str(&row);
+        out.push('\n');
     }
-    let gears = star_adjacent
-        .iter()
-        .filter(|(_pos, numbers)| numbers.len() == 2)
-        .map(|(_key, numbers)| numbers.iter().product::<u64>())
-        .sum::<u64>();
-    Ok(gears)
+    out
 }
@@ -1,68 +1,260 @@
 use anyhow::{Context, Error, Result};
+use rand::{
+    seq::{index::sample, SliceRandom},
+    Rng,
+};
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt, str::FromStr};
 
-use crate::filemanip::read_lines;
-
-pub fn calculate_scratchcard_sum(file: &std::path::Path) -> Result<u64> {
-    let mut sum = 0u64;
-    for line in read_lines(file)
-        .context(format!("Failed to read file {:#?}", file))?
-        .flatten()
-    {
-        let (_card, info) = line.split_once(": ").ok_or(Error::msg("Malformed input"))?;
-        let (winning_numbers, my_numbers) = info
-            .split_once(" | ")
-            .ok_or(Error::msg("Malformed input"))?;
-        let winning_numbers = winning_numbers
-            .split(' ')
-            .flat_map(|s| s.trim().parse::<u64>())
-            .collect::<Vec<_>>();
-        let my_winning_numbers_count = my_numbers
-            .split(' ')
-            .flat_map(|s| s.trim().parse::<u64>())
-            .filter(|x| winning_numbers.contains(x))
-            .count();
-        if my_winning_numbers_count != 0 {
-            sum += 2u64.pow((my_winning_numbers_count - 1) as u32);
+use crate::filemanip::{at, read_numbered_lines, ReadOptions};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Scratchcard {
+    id: u64,
+    winning_numbers: Vec<u64>,
+    numbers: Vec<u64>,
+}
+
+/// Parses one side of a card, rejecting anything that is not a number and numbers that
+/// appear twice.
+fn parse_numbers(side: &str, name: &str) -> Result<Vec<u64>> {
+    let mut numbers = vec![];
+    for token in side.split_whitespace() {
+        let number = token
+            .parse::<u64>()
+            .context(format!("Invalid token {:?} in {}", token, name))?;
+        if numbers.contains(&number) {
+            return Err(Error::msg(format!(
+                "Duplicate number {} in {}",
+                number, name
+            )));
         }
+        numbers.push(number);
+    }
+    Ok(numbers)
+}
+
+impl FromStr for Scratchcard {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, info) = s
+            .strip_prefix("Card")
+            .and_then(|s| s.split_once(':'))
+            .ok_or(Error::msg("Failed to parse card structure"))?;
+        let id = id
+            .trim()
+            .parse::<u64>()
+            .context(format!("Invalid card id {:?}", id.trim()))?;
+        let (winning_numbers, numbers) = info.split_once('|').ok_or(Error::msg(
+            "Missing '|' between winning numbers and numbers",
+        ))?;
+        Ok(Scratchcard {
+            id,
+            winning_numbers: parse_numbers(winning_numbers, "winning numbers")?,
+            numbers: parse_numbers(numbers, "numbers")?,
+        })
     }
-    Ok(sum)
 }
 
-pub fn calculate_scratchcard_count(file: &std::path::Path) -> Result<u64> {
-    let original_cards = read_lines(file)
-        .context(format!("Failed to read file {:#?}", file))?
-        .flatten()
-        .collect::<Vec<_>>();
-    let mut unprocessed_cards: Vec<(usize, &String)> = original_cards
+impl Scratchcard {
+    /// My numbers that are also winning numbers.
+    pub fn matching_numbers(&self) -> impl Iterator<Item = u64> + '_ {
+        self.numbers
+            .iter()
+            .copied()
+            .filter(|x| self.winning_numbers.contains(x))
+    }
+
+    pub fn matches(&self) -> usize {
+        self.matching_numbers().count()
+    }
+
+    pub fn points(&self) -> u64 {
+        match self.matches() {
+            0 => 0,
+            count => 2u64.pow((count - 1) as u32),
+        }
+    }
+}
+
+/// Reads all cards, checking that their ids count up from 1.
+pub fn read_scratchcards(file: &std::path::Path, options: ReadOptions) -> Result<Vec<Scratchcard>> {
+    let mut cards = vec![];
+    for line in read_numbered_lines(file, options)? {
+        let (line_number, line) = line?;
+        let card = line
+            .parse::<Scratchcard>()
+            .context(format!("Failed to parse card {}", at(file, line_number)))?;
+        if card.id != cards.len() as u64 + 1 {
+            return Err(Error::msg(format!(
+                "Card id {} is not sequential, expected {}",
+                card.id,
+                cards.len() + 1
+            ))
+            .context(at(file, line_number)));
+        }
+        cards.push(card);
+    }
+    Ok(cards)
+}
+
+pub fn calculate_scratchcard_sum(file: &std::path::Path, options: ReadOptions) -> Result<u64> {
+    Ok(read_scratchcards(file, options)?
+        .iter()
+        .map(Scratchcard::points)
+        .sum())
+}
+
+/// The range of card indices won by each card.
+fn won_cards(cards: &[Scratchcard]) -> impl Iterator<Item = std::ops::Range<usize>> + '_ {
+    cards
         .iter()
         .enumerate()
-        .collect::<Vec<(usize, &String)>>();
-    let mut count = 0;
-    while !unprocessed_cards.is_empty() {
-        count += 1;
-        let (line_idx, line) = unprocessed_cards
-            .pop()
-            .expect("While condition states this i not empty.");
-        let (_card, info) = line.split_once(": ").ok_or(Error::msg("Malformed input"))?;
-        let (winning_numbers, my_numbers) = info
-            .split_once(" | ")
-            .ok_or(Error::msg("Malformed input"))?;
-        let winning_numbers = winning_numbers
-            .split(' ')
-            .flat_map(|s| s.trim().parse::<u64>())
-            .collect::<Vec<_>>();
-        let my_winning_numbers_count = my_numbers
-            .split(' ')
-            .flat_map(|s| s.trim().parse::<u64>())
-            .filter(|x| winning_numbers.contains(x))
-            .count();
-        if my_winning_numbers_count != 0 {
-            for i in 1..=my_winning_numbers_count {
-                if let Some(other_line) = original_cards.get(line_idx + i) {
-                    unprocessed_cards.push((line_idx + i, other_line));
-                }
-            }
+        .map(|(card_idx, card)| card_idx + 1..(card_idx + 1 + card.matches()).min(cards.len()))
+}
+
+/// The final number of copies of each card, including the original.
+fn count_copies(cards: &[Scratchcard]) -> Vec<u64> {
+    // Every card wins copies of the cards after it, so once we reach a card, all of its
+    // copies are known and can be passed on to the following cards.
+    let mut copies = vec![1u64; cards.len()];
+    for (card_idx, won) in won_cards(cards).enumerate() {
+        for other_idx in won {
+            copies[other_idx] += copies[card_idx];
         }
     }
-    Ok(count as u64)
+    copies
+}
+
+pub fn calculate_scratchcard_count(file: &std::path::Path, options: ReadOptions) -> Result<u64> {
+    Ok(count_copies(&read_scratchcards(file, options)?)
+        .iter()
+        .sum())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardStats {
+    pub id: u64,
+    pub matching_numbers: Vec<u64>,
+    pub points: u64,
+    pub copies: u64,
+    /// The number of card copies won by all copies of this card together.
+    pub produced: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScratchcardReport {
+    pub cards: Vec<CardStats>,
+}
+
+impl ScratchcardReport {
+    /// How many cards have each number of matching numbers.
+    pub fn match_distribution(&self) -> BTreeMap<usize, usize> {
+        let mut distribution = BTreeMap::new();
+        for card in &self.cards {
+            *distribution.entry(card.matching_numbers.len()).or_insert(0) += 1;
+        }
+        distribution
+    }
+
+    /// The card whose copies won the most other cards. Ties go to the earlier card.
+    pub fn most_productive(&self) -> Option<&CardStats> {
+        self.cards.iter().rev().max_by_key(|card| card.produced)
+    }
+}
+
+impl fmt::Display for ScratchcardReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>6} {:>7} {:>10} {:>10}  matching numbers",
+            "card", "points", "copies", "produced"
+        )?;
+        for card in &self.cards {
+            writeln!(
+                f,
+                "{:>6} {:>7} {:>10} {:>10}  {:?}",
+                card.id, card.points, card.copies, card.produced, card.matching_numbers
+            )?;
+        }
+        writeln!(f, "Cards by number of matches:")?;
+        for (matches, count) in self.match_distribution() {
+            writeln!(f, "{:>6}: {}", matches, count)?;
+        }
+        if let Some(card) = self.most_productive() {
+            writeln!(
+                f,
+                "Most copies produced by card {}: {}",
+                card.id, card.produced
+            )?;
+        }
+        writeln!(
+            f,
+            "Total points: {}",
+            self.cards.iter().map(|card| card.points).sum::<u64>()
+        )?;
+        writeln!(
+            f,
+            "Total cards: {}",
+            self.cards.iter().map(|card| card.copies).sum::<u64>()
+        )
+    }
+}
+
+pub fn scratchcard_report(
+    file: &std::path::Path,
+    options: ReadOptions,
+) -> Result<ScratchcardReport> {
+    let cards = read_scratchcards(file, options)?;
+    let copies = count_copies(&cards);
+    let cards = cards
+        .iter()
+        .zip(won_cards(&cards))
+        .zip(copies.iter())
+        .map(|((card, won), copies)| CardStats {
+            id: card.id,
+            matching_numbers: card.matching_numbers().collect(),
+            points: card.points(),
+            copies: *copies,
+            produced: copies * won.len() as u64,
+        })
+        .collect();
+    Ok(ScratchcardReport { cards })
+}
+
+/// Generates `cards` cards with sequential ids, ten winning numbers and twenty-five
+/// numbers each, all between 1 and 99 and distinct within a side.
+///
+/// Most cards have no matching numbers and the rest between one and ten. On average a
+/// card wins fewer than one copy, so the number of copies stays bounded however many
+/// cards are generated.
+pub fn generate_input(rng: &mut impl Rng, cards: usize) -> String {
+    let format_side = |numbers: &[usize]| {
+        numbers
+            .iter()
+            .map(|number| format!("{:>2}", number + 1))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut out = String::new();
+    for id in 1..=cards {
+        let matches = if rng.gen_bool(0.85) {
+            0
+        } else {
+            rng.gen_range(1..=10)
+        };
+        let pool = sample(rng, 99, 35).into_vec();
+        let winning_numbers = &pool[..10];
+        let mut numbers = pool[..matches].to_vec();
+        numbers.extend(&pool[10..35 - matches]);
+        numbers.shuffle(rng);
+        out.push_str(&format!(
+            "Card {:>3}: {} | {}\n",
+            id,
+            format_side(winning_numbers),
+            format_side(&numbers)
+        ));
+    }
+    out
 }
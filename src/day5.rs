@@ -1,11 +1,19 @@
 use anyhow::{Context, Error, Result};
+use rand::{seq::SliceRandom, Rng};
 use rayon::prelude::*;
-use std::{collections::VecDeque, str::FromStr};
+use serde::Serialize;
+use std::{collections::VecDeque, fmt, ops::Range, str::FromStr};
 
-use crate::filemanip::read_lines;
+use crate::{
+    filemanip::{at, InputFile, ReadOptions},
+    progress,
+};
 
-#[derive(Debug, Clone, PartialEq)]
-struct MapEntry {
+/// How many seeds are mapped in parallel between progress reports.
+const SEEDS_PER_REPORT: u64 = 1 << 22;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MapEntry {
     destination_range_start: u64,
     source_range_start: u64,
     range_len: u64,
@@ -43,11 +51,34 @@ impl FromStr for MapEntry {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-struct Map {
+impl fmt::Display for MapEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.destination_range_start, self.source_range_start, self.range_len
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Map {
+    /// The name in the map's header, such as `seed-to-soil`.
+    name: String,
     entries: Vec<MapEntry>,
 }
 
+impl fmt::Display for Map {
+    /// Writes the header line followed by one line per entry, without a trailing newline.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} map:", self.name)?;
+        for entry in &self.entries {
+            write!(f, "\n{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
 impl Map {
     pub fn map(&self, source: u64) -> u64 {
         self.entries
@@ -57,38 +88,96 @@ impl Map {
             .unwrap_or(source)
     }
 
-    fn consume_map(input: &mut VecDeque<String>) -> Result<Map> {
+    /// Maps a range of sources to the ranges of destinations they end up at, splitting it
+    /// where it crosses the boundaries of entries.
+    pub fn map_range(&self, range: Range<u64>) -> Vec<Range<u64>> {
+        let mut unmapped = vec![range];
+        let mut mapped = vec![];
+        for entry in &self.entries {
+            let source_range_end = entry.source_range_start + entry.range_len;
+            let mut remaining = vec![];
+            for range in unmapped {
+                let start = range.start.max(entry.source_range_start);
+                let end = range.end.min(source_range_end);
+                if start >= end {
+                    remaining.push(range);
+                    continue;
+                }
+                let offset = start - entry.source_range_start;
+                mapped.push(
+                    entry.destination_range_start + offset
+                        ..entry.destination_range_start + offset + (end - start),
+                );
+                if range.start < start {
+                    remaining.push(range.start..start);
+                }
+                if end < range.end {
+                    remaining.push(end..range.end);
+                }
+            }
+            unmapped = remaining;
+        }
+        mapped.extend(unmapped);
+        mapped
+    }
+
+    fn consume_map(lines: &mut VecDeque<(usize, &str)>, file: &std::path::Path) -> Result<Map> {
         let mut entries: Vec<MapEntry> = vec![];
-        input.pop_front().expect("Required name for map");
-        while input.front().is_some_and(|line| !line.is_empty()) {
-            let line = input.pop_front().expect("while condition");
-            entries.push(line.parse()?);
+        let (_, name) = lines
+            .pop_front()
+            .ok_or(Error::msg("Required name for map"))?;
+        let name = name.strip_suffix(" map:").unwrap_or(name).to_owned();
+        while lines.front().is_some_and(|(_, line)| !line.is_empty()) {
+            let (line_number, line) = lines.pop_front().expect("while condition");
+            entries.push(line.parse().context(format!(
+                "Failed to parse map entry {}",
+                at(file, line_number)
+            ))?);
         }
-        input.pop_front();
-        Ok(Map { entries })
+        lines.pop_front();
+        Ok(Map { name, entries })
     }
 }
 
-pub fn calculate_lowest_location_number(file: &std::path::Path) -> Result<u64> {
-    let mut raw_data = read_lines(file)
-        .context(format!("Failed to read file {:#?}", file))?
-        .flatten()
-        .collect::<VecDeque<_>>();
-    let seeds = raw_data
-        .pop_front()
-        .ok_or(Error::msg("No seeds found."))?
+/// The seed numbers and the seven maps from seeds to locations.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Almanac {
+    seeds: Vec<u64>,
+    maps: Vec<Map>,
+}
+
+pub fn read_almanac(file: &std::path::Path, options: ReadOptions) -> Result<Almanac> {
+    let input = InputFile::open(file, options)?;
+    let mut raw_data = input.lines().collect::<VecDeque<_>>();
+    let (line_number, seeds) = raw_data.pop_front().ok_or(Error::msg("No seeds found."))?;
+    let seeds = seeds
         .split_once(": ")
-        .ok_or(Error::msg("Malformed input"))?
+        .ok_or(Error::msg("Malformed input"))
+        .context(input.at(line_number))?
         .1
-        .split(' ')
-        .flat_map(|s| s.parse::<u64>())
-        .collect::<Vec<_>>();
+        .split_whitespace()
+        .map(|s| {
+            s.parse::<u64>().context(format!(
+                "Failed to parse seed {:?} {}",
+                s,
+                input.at(line_number)
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
     raw_data.pop_front();
 
     let mut maps = vec![];
     for _ in 0..7 {
-        maps.push(Map::consume_map(&mut raw_data)?);
+        maps.push(Map::consume_map(&mut raw_data, file)?);
     }
+    Ok(Almanac { seeds, maps })
+}
+
+pub fn calculate_lowest_location_number(
+    file: &std::path::Path,
+    options: ReadOptions,
+) -> Result<u64> {
+    let Almanac { seeds, maps } = read_almanac(file, options)?;
 
     let mut min_location = u64::MAX;
     for seed in seeds {
@@ -101,36 +190,203 @@ pub fn calculate_lowest_location_number(file: &std::path::Path) -> Result<u64> {
     Ok(min_location)
 }
 
-pub fn calculate_lowest_location_number_range(file: &std::path::Path) -> Result<u64> {
-    let mut raw_data = read_lines(file)
-        .context(format!("Failed to read file {:#?}", file))?
-        .flatten()
-        .collect::<VecDeque<_>>();
-    let raw_seeds = raw_data
-        .pop_front()
-        .ok_or(Error::msg("No seeds found."))?
-        .split_once(": ")
-        .ok_or(Error::msg("Malformed input"))?
-        .1
-        .split(' ')
-        .flat_map(|s| s.parse::<u64>())
+pub fn calculate_lowest_location_number_range(
+    file: &std::path::Path,
+    options: ReadOptions,
+) -> Result<u64> {
+    let Almanac {
+        seeds: raw_seeds,
+        maps,
+    } = read_almanac(file, options)?;
+    let mut min_location = None;
+    let mut processed = 0;
+    for seed_range in raw_seeds.chunks_exact(2) {
+        let end = seed_range[0] + seed_range[1];
+        let mut start = seed_range[0];
+        while start < end {
+            let batch_end = end.min(start + SEEDS_PER_REPORT);
+            let batch_min = (start..batch_end)
+                .into_par_iter()
+                .map(|seed| maps.iter().fold(seed, |a, map| map.map(a)))
+                .min();
+            min_location = min_location.into_iter().chain(batch_min).min();
+            processed += batch_end - start;
+            progress::report(processed, min_location)?;
+            start = batch_end;
+        }
+    }
+
+    min_location.ok_or(Error::msg("No seeds found."))
+}
+
+const MAP_NAMES: &[&str] = &[
+    "seed-to-soil",
+    "soil-to-fertilizer",
+    "fertilizer-to-water",
+    "water-to-light",
+    "light-to-temperature",
+    "temperature-to-humidity",
+    "humidity-to-location",
+];
+
+/// Generates an almanac with `seed_ranges` seed ranges and `map_entries` entries per map.
+/// Seed ranges and map entries are at most `max_len` long, and the source ranges within
+/// a map never overlap. Seeds, sources and destinations all lie in a window of about
+/// `(map_entries + 1) * max_len` values, so that seeds and destinations fall into, between
+/// and across the entries of the next map.
+pub fn generate_input(
+    rng: &mut impl Rng,
+    seed_ranges: usize,
+    map_entries: usize,
+    max_len: u64,
+) -> String {
+    let window = (map_entries as u64 + 1) * max_len;
+    let seeds = (0..seed_ranges)
+        .map(|_| {
+            format!(
+                "{} {}",
+                rng.gen_range(0..window),
+                rng.gen_range(1..=max_len)
+            )
+        })
         .collect::<Vec<_>>();
-    let seeds = raw_seeds.par_chunks_exact(2).flat_map(|seed_range| {
-        let start = *seed_range.first().unwrap();
-        let len = *seed_range.get(1).unwrap();
-        start..start + len
+    let mut out = format!("seeds: {}\n", seeds.join(" "));
+    for name in MAP_NAMES {
+        // Entries and the gaps between them average `max_len` together, so the entries
+        // span about the same window as the values they map.
+        let mut source_range_start = rng.gen_range(0..=max_len);
+        let mut entries = vec![];
+        for _ in 0..map_entries {
+            let range_len = rng.gen_range(1..=max_len);
+            entries.push(MapEntry {
+                destination_range_start: rng.gen_range(0..window),
+                source_range_start,
+                range_len,
+            });
+            source_range_start += range_len + rng.gen_range(0..=max_len);
+        }
+        entries.shuffle(rng);
+        let map = Map {
+            name: name.to_string(),
+            entries,
+        };
+        out.push_str(&format!("\n{}\n", map));
+    }
+    out
+}
+
+/// Finds the lowest location by mapping whole ranges of seeds at once instead of every seed
+/// on its own. If `ranges` is false, every seed number is a range of length one.
+pub fn calculate_lowest_location_number_intervals(
+    file: &std::path::Path,
+    options: ReadOptions,
+    ranges: bool,
+) -> Result<u64> {
+    let Almanac {
+        seeds: raw_seeds,
+        maps,
+    } = read_almanac(file, options)?;
+    let seeds = if ranges {
+        raw_seeds
+            .chunks_exact(2)
+            .map(|seed_range| seed_range[0]..seed_range[0] + seed_range[1])
+            .collect::<Vec<_>>()
+    } else {
+        raw_seeds.iter().map(|seed| *seed..seed + 1).collect()
+    };
+    let locations = maps.iter().fold(seeds, |ranges, map| {
+        ranges
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .flat_map(|range| map.map_range(range))
+            .collect()
     });
-    raw_data.pop_front();
+    Ok(locations
+        .iter()
+        .filter(|range| !range.is_empty())
+        .map(|range| range.start)
+        .min()
+        .unwrap_or(u64::MAX))
+}
 
-    let mut maps = vec![];
-    for _ in 0..7 {
-        maps.push(Map::consume_map(&mut raw_data)?);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::{collection, prelude::*};
+
+    // Small enough that no start plus length overflows.
+    const MAX_VALUE: u64 = 1 << 40;
+
+    fn map_entry() -> impl Strategy<Value = MapEntry> {
+        (0..MAX_VALUE, 0..MAX_VALUE, 0..MAX_VALUE).prop_map(
+            |(destination_range_start, source_range_start, range_len)| MapEntry {
+                destination_range_start,
+                source_range_start,
+                range_len,
+            },
+        )
     }
 
-    let min_location = seeds
-        .map(|seed| maps.iter().fold(seed, |a, map| map.map(a)))
-        .min()
-        .unwrap();
+    /// Maps with entries clustered around a few small values, so that sources often fall
+    /// into, between and across the boundaries of entries.
+    fn map() -> impl Strategy<Value = Map> {
+        collection::vec(
+            (0..200u64, 0..200u64, 0..50u64).prop_map(
+                |(destination_range_start, source_range_start, range_len)| MapEntry {
+                    destination_range_start,
+                    source_range_start,
+                    range_len,
+                },
+            ),
+            0..6,
+        )
+        .prop_map(|entries| Map {
+            name: "seed-to-soil".to_owned(),
+            entries,
+        })
+    }
 
-    Ok(min_location)
+    proptest! {
+        #[test]
+        fn map_entry_round_trip(entry in map_entry()) {
+            prop_assert_eq!(entry.to_string().parse::<MapEntry>().unwrap(), entry);
+        }
+
+        #[test]
+        fn map_entry_shifts_its_sources(entry in map_entry(), offset in 0..MAX_VALUE) {
+            let source = entry.source_range_start + offset;
+            let expected = (offset < entry.range_len).then_some(entry.destination_range_start + offset);
+            prop_assert_eq!(entry.try_map(source), expected);
+        }
+
+        #[test]
+        fn map_round_trip(map in map()) {
+            let text = map.to_string();
+            let mut lines = text.lines().enumerate().map(|(idx, line)| (idx + 1, line)).collect();
+            let map_file = std::path::Path::new("almanac");
+            prop_assert_eq!(Map::consume_map(&mut lines, map_file).unwrap(), map);
+        }
+
+        #[test]
+        fn map_is_identity_outside_entries(map in map(), source in 0..300u64) {
+            prop_assume!(map.entries.iter().all(|entry| entry.try_map(source).is_none()));
+            prop_assert_eq!(map.map(source), source);
+        }
+
+        #[test]
+        fn map_uses_first_matching_entry(map in map(), source in 0..300u64) {
+            if let Some(destination) = map.entries.iter().find_map(|entry| entry.try_map(source)) {
+                prop_assert_eq!(map.map(source), destination);
+            }
+        }
+
+        #[test]
+        fn map_range_agrees_with_map(map in map(), start in 0..300u64, len in 0..100u64) {
+            let mut expected = (start..start + len).map(|source| map.map(source)).collect::<Vec<_>>();
+            let mut mapped = map.map_range(start..start + len).into_iter().flatten().collect::<Vec<_>>();
+            expected.sort_unstable();
+            mapped.sort_unstable();
+            prop_assert_eq!(mapped, expected);
+        }
+    }
 }
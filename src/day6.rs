@@ -1,8 +1,10 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Error, Result};
+use rand::Rng;
+use serde::Serialize;
 
-use crate::filemanip::read_lines;
+use crate::filemanip::{at, read_numbered_lines, ReadOptions};
 
-fn parse_line(line: &str, kerning: bool) -> Vec<u64> {
+fn parse_line(line: &str, kerning: bool) -> Result<Vec<u64>> {
     line.split_once(": ")
         .map(|(_, times)| {
             if kerning {
@@ -11,10 +13,10 @@ fn parse_line(line: &str, kerning: bool) -> Vec<u64> {
                 times.trim().to_owned()
             }
         })
-        .unwrap()
-        .split(' ')
-        .flat_map(|x| x.parse::<u64>())
-        .collect::<Vec<_>>()
+        .ok_or(Error::msg("Malformed input"))?
+        .split_whitespace()
+        .map(|x| x.parse().context(format!("Failed to parse number {:?}", x)))
+        .collect()
 }
 
 fn hold_time_to_distance(hold_time: u64, max_time: u64) -> u64 {
@@ -25,19 +27,45 @@ fn hold_time_to_distance(hold_time: u64, max_time: u64) -> u64 {
     travel_time * hold_time
 }
 
-pub fn calculate_margin_of_error(file: &std::path::Path, kerning: bool) -> Result<u64> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Race {
+    pub time: u64,
+    pub record: u64,
+}
+
+/// Reads all races. With `kerning`, the numbers on each line form a single race.
+pub fn read_races(
+    file: &std::path::Path,
+    options: ReadOptions,
+    kerning: bool,
+) -> Result<Vec<Race>> {
+    let mut data = read_numbered_lines(file, options)?;
+    let (line_number, times) = data.next().ok_or(Error::msg("No times found."))??;
+    let times = parse_line(&times, kerning).context(at(file, line_number))?;
+    let (line_number, distances) = data.next().ok_or(Error::msg("No distances found."))??;
+    let distances = parse_line(&distances, kerning).context(at(file, line_number))?;
+    Ok(times
+        .into_iter()
+        .zip(distances)
+        .map(|(time, record)| Race { time, record })
+        .collect())
+}
+
+pub fn calculate_margin_of_error(
+    file: &std::path::Path,
+    options: ReadOptions,
+    kerning: bool,
+) -> Result<u64> {
     let mut product = 1u64;
-    let data = read_lines(file)
-        .context(format!("Failed to read file {:#?}", file))?
-        .flatten()
-        .collect::<Vec<_>>();
-    let times = parse_line(data.get(0).unwrap(), kerning);
-    let distances = parse_line(data.get(1).unwrap(), kerning);
-    for (time, min_distance) in times.iter().zip(distances.iter()) {
+    for Race {
+        time,
+        record: min_distance,
+    } in read_races(file, options, kerning)?
+    {
         let mut count = 0;
-        for hold_duration in 1..*time {
-            let distance = hold_time_to_distance(hold_duration, *time);
-            if distance > *min_distance {
+        for hold_duration in 1..time {
+            let distance = hold_time_to_distance(hold_duration, time);
+            if distance > min_distance {
                 count += 1;
             }
         }
@@ -45,3 +73,67 @@ pub fn calculate_margin_of_error(file: &std::path::Path, kerning: bool) -> Resul
     }
     Ok(product)
 }
+
+/// Counts the hold times that beat `record` by solving `hold * (time - hold) > record` for
+/// `hold`. The winning hold times lie symmetrically around `time / 2`, so only the lowest
+/// one is needed.
+fn count_winning_hold_times(time: u64, record: u64) -> u64 {
+    let discriminant = (time as f64).powi(2) - 4.0 * record as f64;
+    if discriminant < 0.0 {
+        return 0;
+    }
+    // The float root can be off by one either way, so nudge it onto the exact boundary.
+    let mut low = ((time as f64 - discriminant.sqrt()) / 2.0).floor().max(0.0) as u64;
+    while low > 0 && hold_time_to_distance(low - 1, time) > record {
+        low -= 1;
+    }
+    while low <= time / 2 && hold_time_to_distance(low, time) <= record {
+        low += 1;
+    }
+    if low > time / 2 {
+        return 0;
+    }
+    time - 2 * low + 1
+}
+
+pub fn calculate_margin_of_error_closed_form(
+    file: &std::path::Path,
+    options: ReadOptions,
+    kerning: bool,
+) -> Result<u64> {
+    Ok(read_races(file, options, kerning)?
+        .into_iter()
+        .map(|race| count_winning_hold_times(race.time, race.record))
+        .product())
+}
+
+/// Generates `races` races lasting up to 100 ms, each with a record distance that can be
+/// beaten.
+pub fn generate_input(rng: &mut impl Rng, races: usize) -> String {
+    let mut times = vec![];
+    let mut distances = vec![];
+    for _ in 0..races {
+        let time = rng.gen_range(2..=100u64);
+        times.push(time);
+        distances.push(rng.gen_range(0..hold_time_to_distance(time / 2, time)));
+    }
+    let width = times
+        .iter()
+        .chain(distances.iter())
+        .max()
+        .unwrap_or(&0)
+        .to_string()
+        .len()
+        + 2;
+    let format_line = |values: &[u64]| {
+        values
+            .iter()
+            .map(|value| format!("{:>width$}", value))
+            .collect::<String>()
+    };
+    format!(
+        "Time:    {}\nDistance:{}\n",
+        format_line(&times),
+        format_line(&distances)
+    )
+}
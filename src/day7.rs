@@ -1,8 +1,10 @@
-use std::{cmp, collections::HashMap, str::FromStr};
+use std::{cmp, collections::HashMap, fmt, str::FromStr};
 
 use anyhow::{Context, Error, Result};
+use rand::Rng;
+use serde::Serialize;
 
-use crate::filemanip::read_lines;
+use crate::filemanip::{at, read_numbered_lines, ReadOptions};
 
 const CARDS_PART_ONE: &[char] = &[
     '2', '3', '4', '5', '6', '7', '8', '9', 'T', 'J', 'Q', 'K', 'A',
@@ -12,23 +14,28 @@ const CARDS_PART_TWO: &[char] = &[
     'J', '2', '3', '4', '5', '6', '7', '8', '9', 'T', 'Q', 'K', 'A',
 ];
 
+const TYPE_NAMES: &[&str] = &[
+    "high card",
+    "one pair",
+    "two pair",
+    "three of a kind",
+    "full house",
+    "four of a kind",
+    "five of a kind",
+];
+
 #[derive(PartialEq, Eq, Debug)]
 struct Hand {
+    /// Positions of the cards in `order`, so that higher cards have higher values.
     cards: Vec<u8>,
+    /// The card order the hand was parsed with, [`CARDS_PART_ONE`] or [`CARDS_PART_TWO`].
+    order: &'static [char],
     r#type: u8,
 }
 
-impl FromStr for Hand {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<Self> {
-        if s.len() != 5 {
-            return Err(Error::msg("Invalid string length supplied"));
-        }
-        let cards = s
-            .chars()
-            .map(|c| CARDS_PART_TWO.iter().position(|x| *x == c).unwrap() as u8)
-            .collect::<Vec<_>>();
+impl Hand {
+    /// Builds a hand from card indices into `order`, determining its type.
+    fn from_cards(cards: Vec<u8>, order: &'static [char]) -> Self {
         let mut counts = HashMap::new();
         for card in &cards {
             if !counts.contains_key(card) {
@@ -51,7 +58,47 @@ impl FromStr for Hand {
         } else {
             r#type = 0; // High card
         }
-        Ok(Hand { cards, r#type })
+        Hand {
+            cards,
+            order,
+            r#type,
+        }
+    }
+
+    /// Parses a hand, ranking its cards by their position in `order`.
+    fn parse_in_order(s: &str, order: &'static [char]) -> Result<Self> {
+        if s.len() != 5 {
+            return Err(Error::msg("Invalid string length supplied"));
+        }
+        let cards = s
+            .chars()
+            .map(|c| {
+                order
+                    .iter()
+                    .position(|x| *x == c)
+                    .map(|card| card as u8)
+                    .ok_or(Error::msg(format!("Invalid card {:?}", c)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Hand::from_cards(cards, order))
+    }
+}
+
+impl fmt::Display for Hand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for card in &self.cards {
+            write!(f, "{}", self.order[*card as usize])?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Hand {
+    type Err = anyhow::Error;
+
+    /// Parses a hand in the card order of challenge 2, where jokers are the lowest card.
+    fn from_str(s: &str) -> Result<Self> {
+        Hand::parse_in_order(s, CARDS_PART_TWO)
     }
 }
 
@@ -75,36 +122,209 @@ impl Ord for Hand {
     }
 }
 
-fn replace_jokers(hand: &str) -> Hand {
-    let mut real_hand = hand.parse::<Hand>().unwrap();
+/// Parses a hand in the card order of challenge 1, where jacks rank between tens and
+/// queens.
+fn parse_without_jokers(hand: &str) -> Result<Hand> {
+    Hand::parse_in_order(hand, CARDS_PART_ONE)
+}
+
+fn replace_jokers(hand: &str) -> Result<Hand> {
+    let mut real_hand = hand.parse::<Hand>()?;
     let mut max_types = vec![];
     for sub in CARDS_PART_TWO {
-        max_types.push(hand.replace('J', &sub.to_string()).parse::<Hand>().unwrap());
+        max_types.push(hand.replace('J', &sub.to_string()).parse::<Hand>()?);
     }
     let max_type = max_types.iter().map(|hand| hand.r#type).max().unwrap();
     real_hand.r#type = max_type;
-    real_hand
+    Ok(real_hand)
 }
 
-pub fn calculate_winning_hands(file: &std::path::Path, jokers: bool) -> Result<u64> {
-    let mut cards = read_lines(file)
-        .context(format!("Failed to read file {:#?}", file))?
-        .flatten()
+/// Determines the best type of a hand by adding its jokers to the card it has most of,
+/// instead of trying every substitution.
+fn count_jokers(hand: &str) -> Result<Hand> {
+    let mut real_hand = hand.parse::<Hand>()?;
+    let mut counts = [0usize; 13];
+    for card in &real_hand.cards {
+        counts[*card as usize] += 1;
+    }
+    // Jokers are the lowest card in CARDS_PART_TWO.
+    let jokers = counts[0];
+    let mut counts = counts[1..]
+        .iter()
+        .copied()
+        .filter(|count| *count > 0)
+        .collect::<Vec<_>>();
+    counts.sort_unstable_by(|a, b| b.cmp(a));
+    match counts.first_mut() {
+        Some(most) => *most += jokers,
+        None => counts.push(jokers),
+    }
+    real_hand.r#type = match counts.as_slice() {
+        [5] => 6,
+        [4, 1] => 5,
+        [3, 2] => 4,
+        [3, 1, 1] => 3,
+        [2, 2, 1] => 2,
+        [2, 1, 1, 1] => 1,
+        _ => 0,
+    };
+    Ok(real_hand)
+}
+
+/// Reads all hands, parsed with `parse_hand`, together with their bids.
+fn read_hands(
+    file: &std::path::Path,
+    options: ReadOptions,
+    parse_hand: fn(&str) -> Result<Hand>,
+) -> Result<Vec<(Hand, u64)>> {
+    read_numbered_lines(file, options)?
         .map(|line| {
-            let (hand, bid) = line.split_once(' ').unwrap();
-            let parsed_hand = if jokers {
-                replace_jokers(hand)
-            } else {
-                hand.parse().unwrap()
-            };
-            (parsed_hand, bid.parse::<u64>().unwrap())
+            let (line_number, line) = line?;
+            let (hand, bid) = line
+                .split_once(' ')
+                .ok_or(Error::msg("Malformed input"))
+                .context(at(file, line_number))?;
+            let parsed_hand = parse_hand(hand)
+                .context(format!("Failed to parse hand {}", at(file, line_number)))?;
+            let bid = bid
+                .parse::<u64>()
+                .context(format!("Failed to parse bid {}", at(file, line_number)))?;
+            Ok((parsed_hand, bid))
         })
-        .collect::<Vec<_>>();
+        .collect()
+}
+
+/// A hand and its bid as written in the input, with the hand's type without and with
+/// jokers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Play {
+    pub hand: String,
+    pub r#type: &'static str,
+    pub joker_type: &'static str,
+    pub bid: u64,
+}
+
+pub fn read_plays(file: &std::path::Path, options: ReadOptions) -> Result<Vec<Play>> {
+    read_hands(file, options, str::parse)?
+        .into_iter()
+        .map(|(hand, bid)| {
+            let cards = hand.to_string();
+            let joker_type = count_jokers(&cards)?.r#type;
+            Ok(Play {
+                r#type: TYPE_NAMES[hand.r#type as usize],
+                joker_type: TYPE_NAMES[joker_type as usize],
+                hand: cards,
+                bid,
+            })
+        })
+        .collect()
+}
+
+/// Ranks all hands parsed with `parse_hand` and sums their bids multiplied by their rank.
+fn total_winnings(
+    file: &std::path::Path,
+    options: ReadOptions,
+    parse_hand: fn(&str) -> Result<Hand>,
+) -> Result<u64> {
+    let mut cards = read_hands(file, options, parse_hand)?;
     cards.sort_by(|a, b| a.0.cmp(&b.0));
-    dbg!(&cards);
     Ok(cards
         .iter()
         .enumerate()
         .map(|(i, (_, bid))| (i as u64 + 1) * bid)
         .sum())
 }
+
+pub fn calculate_winning_hands(
+    file: &std::path::Path,
+    options: ReadOptions,
+    jokers: bool,
+) -> Result<u64> {
+    if jokers {
+        total_winnings(file, options, replace_jokers)
+    } else {
+        total_winnings(file, options, parse_without_jokers)
+    }
+}
+
+/// Solves challenge 2 like [`calculate_winning_hands`], but counts jokers instead of
+/// substituting them.
+pub fn calculate_winning_hands_counting(
+    file: &std::path::Path,
+    options: ReadOptions,
+) -> Result<u64> {
+    total_winnings(file, options, count_jokers)
+}
+
+/// Generates `hands` random hands with bids between 1 and 1000.
+pub fn generate_input(rng: &mut impl Rng, hands: usize) -> String {
+    let mut out = String::new();
+    for _ in 0..hands {
+        let hand = Hand::from_cards(
+            (0..5)
+                .map(|_| rng.gen_range(0..CARDS_PART_TWO.len() as u8))
+                .collect(),
+            CARDS_PART_TWO,
+        );
+        out.push_str(&format!("{} {}\n", hand, rng.gen_range(1..=1000)));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::{collection, prelude::*, sample::select};
+
+    fn hand() -> impl Strategy<Value = String> {
+        collection::vec(select(CARDS_PART_TWO), 5).prop_map(|cards| cards.into_iter().collect())
+    }
+
+    proptest! {
+        #[test]
+        fn hand_round_trip(hand in hand()) {
+            prop_assert_eq!(hand.parse::<Hand>().unwrap().to_string(), hand);
+        }
+
+        #[test]
+        fn hand_rejects_wrong_length(hand in "[2-9TJQKA]{0,4}|[2-9TJQKA]{6,8}") {
+            prop_assert!(hand.parse::<Hand>().is_err());
+        }
+
+        #[test]
+        fn jokers_never_lower_the_type(hand in hand()) {
+            let plain = hand.parse::<Hand>().unwrap();
+            prop_assert!(replace_jokers(&hand).unwrap().r#type >= plain.r#type);
+        }
+
+        #[test]
+        fn adding_a_joker_never_lowers_the_type(hand in hand(), idx in 0..5usize) {
+            let mut with_joker = hand.chars().collect::<Vec<_>>();
+            with_joker[idx] = 'J';
+            let with_joker = with_joker.into_iter().collect::<String>();
+            prop_assert!(
+                replace_jokers(&with_joker).unwrap().r#type >= replace_jokers(&hand).unwrap().r#type
+            );
+        }
+
+        #[test]
+        fn hand_round_trip_without_jokers(hand in hand()) {
+            prop_assert_eq!(parse_without_jokers(&hand).unwrap().to_string(), hand);
+        }
+
+        #[test]
+        fn jacks_only_rank_below_twos_with_jokers(rest in "[3-9TQKA]{4}") {
+            let with_jack = format!("J{}", rest);
+            let with_two = format!("2{}", rest);
+            prop_assert!(
+                parse_without_jokers(&with_jack).unwrap() > parse_without_jokers(&with_two).unwrap()
+            );
+            prop_assert!(with_jack.parse::<Hand>().unwrap() < with_two.parse::<Hand>().unwrap());
+        }
+
+        #[test]
+        fn counting_jokers_matches_substitution(hand in hand()) {
+            prop_assert_eq!(count_jokers(&hand).unwrap(), replace_jokers(&hand).unwrap());
+        }
+    }
+}
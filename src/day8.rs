@@ -1,36 +1,194 @@
-use std::collections::{HashMap, VecDeque};
-
-use anyhow::{Context, Result};
-
-use crate::filemanip::read_lines;
-
-pub fn calculate_zzz_steps(file: &std::path::Path) -> Result<u64> {
-    let mut lines = read_lines(file)
-        .context(format!("Failed to read file {:#?}", file))?
-        .flatten()
-        .collect::<VecDeque<_>>();
-    let instructions = lines.pop_front().unwrap();
-    let mut instructions = instructions.chars().cycle();
-    let _blank = lines.pop_front().unwrap();
-    let mut nodes = HashMap::new();
-    for line in lines {
-        let (node_name, node_steps) = line.trim().split_once(" = ").unwrap();
-        let (left, right) = node_steps.trim().split_once(", ").unwrap();
-        let left = left.replace("(", "");
-        let right = right.replace(")", "");
-        nodes.insert(node_name.to_owned(), (left, right));
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use anyhow::{Context, Error, Result};
+use rand::{
+    seq::{index::sample, SliceRandom},
+    Rng,
+};
+use serde::Serialize;
+
+use crate::{
+    filemanip::{InputFile, ReadOptions},
+    progress,
+};
+
+/// How many steps are taken between progress reports.
+const STEPS_PER_REPORT: u64 = 1 << 20;
+
+/// Splits a node line such as `AAA = (BBB, CCC)` into its name and left and right
+/// neighbours.
+fn split_node(line: &str) -> Result<(&str, &str, &str)> {
+    let (node_name, left, right) = line
+        .trim()
+        .split_once(" = ")
+        .and_then(|(node_name, node_steps)| {
+            let (left, right) = node_steps.trim().split_once(", ")?;
+            Some((node_name, left, right))
+        })
+        .ok_or(Error::msg("Malformed node"))?;
+    Ok((
+        node_name,
+        left.trim_start_matches('('),
+        right.trim_end_matches(')'),
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Node {
+    pub name: String,
+    pub left: String,
+    pub right: String,
+}
+
+impl FromStr for Node {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, left, right) = split_node(s)?;
+        Ok(Node {
+            name: name.to_owned(),
+            left: left.to_owned(),
+            right: right.to_owned(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Network {
+    pub instructions: String,
+    pub nodes: Vec<Node>,
+}
+
+/// Reads the instructions and nodes, checking that the instructions are a non-empty
+/// sequence of `L` and `R` and that every node only leads to nodes that are defined.
+pub fn read_network(file: &std::path::Path, options: ReadOptions) -> Result<Network> {
+    let input = InputFile::open(file, options)?;
+    let mut lines = input.lines();
+    let (line_number, instructions) = lines.next().ok_or(Error::msg("No instructions found."))?;
+    if instructions.is_empty() {
+        return Err(Error::msg("No instructions found.")).context(input.at(line_number));
+    }
+    if let Some(instruction) = instructions.chars().find(|c| !matches!(c, 'L' | 'R')) {
+        return Err(Error::msg(format!("Invalid instruction {:?}", instruction)))
+            .context(input.at(line_number));
     }
+    let _blank = lines.next();
+    let nodes = lines
+        .map(|(line_number, line)| {
+            Ok((
+                line_number,
+                line.parse::<Node>().context(input.at(line_number))?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    let mut current = "AAA".to_owned();
+    let names = nodes
+        .iter()
+        .map(|(_, node)| node.name.as_str())
+        .collect::<HashSet<_>>();
+    for (line_number, node) in &nodes {
+        if let Some(undefined) = [&node.left, &node.right]
+            .into_iter()
+            .find(|next| !names.contains(next.as_str()))
+        {
+            return Err(Error::msg(format!("Undefined node {:?}", undefined)))
+                .context(input.at(*line_number));
+        }
+    }
+    Ok(Network {
+        instructions: instructions.to_owned(),
+        nodes: nodes.into_iter().map(|(_, node)| node).collect(),
+    })
+}
+
+pub fn calculate_zzz_steps(file: &std::path::Path, options: ReadOptions) -> Result<u64> {
+    let Network {
+        instructions,
+        nodes,
+    } = read_network(file, options)?;
+    let nodes = nodes
+        .iter()
+        .map(|node| (node.name.as_str(), node))
+        .collect::<HashMap<_, _>>();
+
+    let mut current = *nodes
+        .get("AAA")
+        .ok_or(Error::msg(format!("No node AAA in {:#?}", file)))?;
     let mut count = 0u64;
-    while current != "ZZZ" {
-        let instr = instructions.next().unwrap();
-        current = match instr {
-            'L' => nodes.get(&current).unwrap().0.to_owned(),
-            'R' => nodes.get(&current).unwrap().1.to_owned(),
-            _ => panic!("Malformed instruction"),
+    for instruction in instructions.chars().cycle() {
+        if current.name == "ZZZ" {
+            break;
+        }
+        // read_network checked the instructions and that every neighbour is defined.
+        let next = match instruction {
+            'L' => &current.left,
+            _ => &current.right,
         };
+        current = nodes[next.as_str()];
         count += 1;
+        if count.is_multiple_of(STEPS_PER_REPORT) {
+            progress::report(count, None)?;
+        }
     }
     Ok(count)
 }
+
+/// Generates a network of `nodes` nodes, at least two, in which following the
+/// instructions from `AAA` leads to `ZZZ`.
+pub fn generate_input(rng: &mut impl Rng, nodes: usize) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let name = |idx: usize| {
+        [idx / (26 * 26), idx / 26 % 26, idx % 26]
+            .iter()
+            .map(|letter| ALPHABET[*letter] as char)
+            .collect::<String>()
+    };
+    let nodes = nodes.clamp(2, 26 * 26 * 26);
+    // AAA and ZZZ are the first and last name, the others are drawn from in between.
+    let mut names = vec![name(0)];
+    names.extend(
+        sample(rng, 26 * 26 * 26 - 2, nodes - 2)
+            .iter()
+            .map(|idx| name(idx + 1)),
+    );
+    names.push(name(26 * 26 * 26 - 1));
+
+    let instructions = (0..rng.gen_range(1..=20))
+        .map(|_| if rng.gen_bool(0.5) { 'L' } else { 'R' })
+        .collect::<Vec<_>>();
+
+    // The path from AAA visits some of the other nodes, each exactly once, before ZZZ.
+    let mut path = vec![0];
+    let mut others = (1..nodes - 1).collect::<Vec<_>>();
+    others.shuffle(rng);
+    path.extend(&others[..rng.gen_range(0..=others.len())]);
+    path.push(nodes - 1);
+
+    let mut edges = (0..nodes)
+        .map(|_| (rng.gen_range(0..nodes), rng.gen_range(0..nodes)))
+        .collect::<Vec<_>>();
+    for (step, window) in path.windows(2).enumerate() {
+        let (from, to) = (window[0], window[1]);
+        match instructions[step % instructions.len()] {
+            'L' => edges[from].0 = to,
+            _ => edges[from].1 = to,
+        }
+    }
+
+    let mut lines = edges
+        .iter()
+        .enumerate()
+        .map(|(idx, (left, right))| {
+            format!("{} = ({}, {})", names[idx], names[*left], names[*right])
+        })
+        .collect::<Vec<_>>();
+    lines.shuffle(rng);
+    format!(
+        "{}\n\n{}\n",
+        instructions.iter().collect::<String>(),
+        lines.join("\n")
+    )
+}
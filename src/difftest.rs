@@ -0,0 +1,298 @@
+use anyhow::{Context, Error, Result};
+use clap::Args;
+use std::{
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    day5, day6, day7,
+    filemanip::ReadOptions,
+    generate::{self, GeneratorOptions},
+};
+
+/// One way of solving a challenge.
+pub struct Implementation {
+    pub day: u8,
+    pub challenge: u8,
+    pub name: &'static str,
+    pub solve: fn(&Path, ReadOptions) -> Result<u64>,
+}
+
+/// All challenges with more than one implementation.
+pub fn implementations() -> Vec<Implementation> {
+    vec![
+        Implementation {
+            day: 5,
+            challenge: 1,
+            name: "brute-force",
+            solve: day5::calculate_lowest_location_number,
+        },
+        Implementation {
+            day: 5,
+            challenge: 1,
+            name: "intervals",
+            solve: |file, options| {
+                day5::calculate_lowest_location_number_intervals(file, options, false)
+            },
+        },
+        Implementation {
+            day: 5,
+            challenge: 2,
+            name: "brute-force",
+            solve: day5::calculate_lowest_location_number_range,
+        },
+        Implementation {
+            day: 5,
+            challenge: 2,
+            name: "intervals",
+            solve: |file, options| {
+                day5::calculate_lowest_location_number_intervals(file, options, true)
+            },
+        },
+        Implementation {
+            day: 6,
+            challenge: 1,
+            name: "loop",
+            solve: |file, options| day6::calculate_margin_of_error(file, options, false),
+        },
+        Implementation {
+            day: 6,
+            challenge: 1,
+            name: "closed-form",
+            solve: |file, options| {
+                day6::calculate_margin_of_error_closed_form(file, options, false)
+            },
+        },
+        Implementation {
+            day: 6,
+            challenge: 2,
+            name: "loop",
+            solve: |file, options| day6::calculate_margin_of_error(file, options, true),
+        },
+        Implementation {
+            day: 6,
+            challenge: 2,
+            name: "closed-form",
+            solve: |file, options| day6::calculate_margin_of_error_closed_form(file, options, true),
+        },
+        Implementation {
+            day: 7,
+            challenge: 2,
+            name: "substitution",
+            solve: |file, options| day7::calculate_winning_hands(file, options, true),
+        },
+        Implementation {
+            day: 7,
+            challenge: 2,
+            name: "counting",
+            solve: day7::calculate_winning_hands_counting,
+        },
+    ]
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DifftestArgs {
+    /// The day of the challenge
+    pub day: u8,
+
+    /// The challenge number
+    pub challenge: u8,
+
+    /// The first implementation to compare. Defaults to the first one registered
+    #[arg(long)]
+    pub left: Option<String>,
+
+    /// The second implementation to compare. Defaults to the second one registered
+    #[arg(long)]
+    pub right: Option<String>,
+
+    /// Compare on these recorded inputs instead of generated ones
+    #[arg(long = "input")]
+    pub inputs: Vec<PathBuf>,
+
+    /// Number of inputs to generate, using consecutive seeds starting at --seed
+    #[arg(long, default_value_t = 100)]
+    pub runs: u64,
+
+    #[command(flatten)]
+    pub options: GeneratorOptions,
+}
+
+/// Runs `solve` on `file`, treating errors and panics alike as no answer.
+fn answer(solve: fn(&Path, ReadOptions) -> Result<u64>, file: &Path) -> Option<u64> {
+    panic::catch_unwind(AssertUnwindSafe(|| solve(file, ReadOptions::default())))
+        .ok()
+        .and_then(Result::ok)
+}
+
+/// Two implementations disagree on an input if both produce an answer and the answers
+/// differ. Inputs that either of them rejects say nothing about their agreement.
+fn disagree(
+    left: &Implementation,
+    right: &Implementation,
+    input: &str,
+    file: &Path,
+) -> Result<bool> {
+    fs::write(file, input).context(format!("Failed to write file {:#?}", file))?;
+    Ok(matches!(
+        (answer(left.solve, file), answer(right.solve, file)),
+        (Some(left), Some(right)) if left != right
+    ))
+}
+
+/// Greedily removes chunks of lines, then single whitespace-separated tokens, for as long
+/// as the implementations still disagree on what is left.
+fn shrink(
+    left: &Implementation,
+    right: &Implementation,
+    input: &str,
+    file: &Path,
+) -> Result<String> {
+    let mut lines = input.lines().map(str::to_owned).collect::<Vec<_>>();
+    let join =
+        |lines: &[String]| -> String { lines.iter().map(|line| format!("{}\n", line)).collect() };
+
+    let mut chunk = lines.len().div_ceil(2).max(1);
+    loop {
+        let mut start = 0;
+        while start < lines.len() {
+            let mut candidate = lines.clone();
+            candidate.drain(start..(start + chunk).min(lines.len()));
+            if disagree(left, right, &join(&candidate), file)? {
+                lines = candidate;
+            } else {
+                start += chunk;
+            }
+        }
+        if chunk == 1 {
+            break;
+        }
+        chunk = chunk.div_ceil(2);
+    }
+
+    for line_idx in 0..lines.len() {
+        let mut token_idx = 0;
+        // An empty line has no token to remove, only itself, which the first pass tried.
+        while !lines[line_idx].is_empty() && token_idx < lines[line_idx].split(' ').count() {
+            let mut candidate = lines.clone();
+            candidate[line_idx] = lines[line_idx]
+                .split(' ')
+                .enumerate()
+                .filter(|(idx, _)| *idx != token_idx)
+                .map(|(_, token)| token)
+                .collect::<Vec<_>>()
+                .join(" ");
+            if disagree(left, right, &join(&candidate), file)? {
+                lines = candidate;
+            } else {
+                token_idx += 1;
+            }
+        }
+    }
+    Ok(join(&lines))
+}
+
+fn find<'a>(
+    candidates: &'a [&'a Implementation],
+    name: Option<&str>,
+    default_idx: usize,
+) -> Result<&'a Implementation> {
+    match name {
+        Some(name) => candidates
+            .iter()
+            .find(|implementation| implementation.name == name)
+            .copied()
+            .ok_or(Error::msg(format!("Unknown implementation {:?}", name))),
+        None => candidates.get(default_idx).copied().ok_or(Error::msg(
+            "Fewer than two implementations are registered for this challenge",
+        )),
+    }
+}
+
+/// Compares two implementations of a challenge and reports the first input they disagree
+/// on, shrunk to a minimal case.
+pub fn run_difftest(args: &DifftestArgs) -> Result<()> {
+    let registry = implementations();
+    let candidates = registry
+        .iter()
+        .filter(|implementation| {
+            implementation.day == args.day && implementation.challenge == args.challenge
+        })
+        .collect::<Vec<_>>();
+    let left = find(&candidates, args.left.as_deref(), 0)?;
+    let right = find(&candidates, args.right.as_deref(), 1)?;
+
+    let inputs: Box<dyn Iterator<Item = Result<(String, String)>>> = if args.inputs.is_empty() {
+        Box::new((0..args.runs).map(|run| {
+            let options = GeneratorOptions {
+                seed: args.options.seed + run,
+                ..args.options.clone()
+            };
+            let input = generate::generate_input(args.day, &options)?;
+            Ok((format!("generated input with seed {}", options.seed), input))
+        }))
+    } else {
+        Box::new(args.inputs.iter().map(|path| {
+            let input =
+                fs::read_to_string(path).context(format!("Failed to read file {:#?}", path))?;
+            Ok((format!("{:#?}", path), input))
+        }))
+    };
+
+    let file = std::env::temp_dir().join(format!("difftest-{}.txt", std::process::id()));
+    // Solvers that panic on odd inputs are expected here, so keep their messages quiet.
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let mut checked = 0;
+    let mut outcome = Ok(None);
+    for input in inputs {
+        let (source, input) = match input {
+            Ok(input) => input,
+            Err(err) => {
+                outcome = Err(err);
+                break;
+            }
+        };
+        checked += 1;
+        match disagree(left, right, &input, &file) {
+            Ok(false) => {}
+            Ok(true) => {
+                outcome = shrink(left, right, &input, &file).and_then(|minimized| {
+                    fs::write(&file, &minimized)
+                        .context(format!("Failed to write file {:#?}", file))?;
+                    let answers = (answer(left.solve, &file), answer(right.solve, &file));
+                    Ok(Some((source, minimized, answers)))
+                });
+                break;
+            }
+            Err(err) => {
+                outcome = Err(err);
+                break;
+            }
+        }
+    }
+    panic::set_hook(hook);
+    let _ = fs::remove_file(&file);
+
+    match outcome? {
+        None => {
+            println!(
+                "{} and {} agree on all {} inputs",
+                left.name, right.name, checked
+            );
+            Ok(())
+        }
+        Some((source, minimized, (left_answer, right_answer))) => {
+            println!("Disagreement on {}, minimized to:", source);
+            print!("{}", minimized);
+            println!("{}: {:?}", left.name, left_answer);
+            println!("{}: {:?}", right.name, right_answer);
+            Err(Error::msg(format!(
+                "Implementations {} and {} disagree",
+                left.name, right.name
+            )))
+        }
+    }
+}
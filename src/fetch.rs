@@ -0,0 +1,238 @@
+use anyhow::{Context, Error, Result};
+use clap::Args;
+use std::{
+    fs,
+    io::{Read, Write},
+    net::{IpAddr, TcpStream},
+    path::PathBuf,
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+const USER_AGENT: &str = "advent_of_code input fetcher";
+
+/// Something that can download a page.
+pub trait HttpBackend {
+    /// Fetches `url`, sending `session` as the session cookie, and returns the body of a
+    /// successful response.
+    fn get(&self, url: &str, session: Option<&str>) -> Result<String>;
+}
+
+/// Downloads with the `curl` command, which supports HTTPS.
+pub struct Curl;
+
+impl HttpBackend for Curl {
+    fn get(&self, url: &str, session: Option<&str>) -> Result<String> {
+        // The cookie is passed on stdin so that the token does not show up in process lists.
+        let mut child = Command::new("curl")
+            .args(["--fail", "--silent", "--show-error", "--location"])
+            .args(["--user-agent", USER_AGENT, "--header", "@-", url])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to run curl")?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        if let Some(session) = session {
+            writeln!(stdin, "Cookie: session={}", session)?;
+        }
+        drop(stdin);
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(Error::msg(format!(
+                "Failed to fetch {}: {}",
+                url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        String::from_utf8(output.stdout).context(format!("Response from {} is not UTF-8", url))
+    }
+}
+
+/// Downloads over plain HTTP without any dependencies, for local mock servers. The session
+/// cookie would travel in cleartext, so it is only sent to loopback hosts.
+pub struct PlainHttp;
+
+/// Whether `host`, with or without a port, names this machine.
+fn is_loopback(host: &str) -> bool {
+    // Like the connection address, a colon separates the host from its port.
+    let name = host.rsplit_once(':').map_or(host, |(name, _)| name);
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    name == "localhost" || name.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+impl HttpBackend for PlainHttp {
+    fn get(&self, url: &str, session: Option<&str>) -> Result<String> {
+        let rest = url.strip_prefix("http://").ok_or(Error::msg(format!(
+            "The http backend only supports http:// URLs, not {}",
+            url
+        )))?;
+        let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        let path = if path.is_empty() { "/" } else { path };
+        if session.is_some() && !is_loopback(host) {
+            return Err(Error::msg(format!(
+                "Refusing to send the session token to {} over plain HTTP",
+                host
+            )));
+        }
+        let address = if host.contains(':') {
+            host.to_owned()
+        } else {
+            format!("{}:80", host)
+        };
+
+        let mut stream =
+            TcpStream::connect(&address).context(format!("Failed to connect to {}", address))?;
+        // HTTP/1.0 keeps servers from sending chunked responses, so the body is everything
+        // after the headers.
+        let mut request = format!(
+            "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: {}\r\n",
+            path, host, USER_AGENT
+        );
+        if let Some(session) = session {
+            request.push_str(&format!("Cookie: session={}\r\n", session));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        let mut response = vec![];
+        stream
+            .read_to_end(&mut response)
+            .context(format!("Failed to read response from {}", url))?;
+
+        let response =
+            String::from_utf8(response).context(format!("Response from {} is not UTF-8", url))?;
+        let (head, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or(Error::msg(format!("Malformed response from {}", url)))?;
+        let status = head.lines().next().unwrap_or_default();
+        if status.split_whitespace().nth(1) != Some("200") {
+            return Err(Error::msg(format!("Failed to fetch {}: {}", url, status)));
+        }
+        Ok(body.to_owned())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Curl,
+    Http,
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "curl" => Ok(Backend::Curl),
+            "http" => Ok(Backend::Http),
+            _ => Err(Error::msg(format!("Unknown HTTP backend {:?}", s))),
+        }
+    }
+}
+
+impl Backend {
+    pub fn client(&self) -> Box<dyn HttpBackend> {
+        match self {
+            Backend::Curl => Box::new(Curl),
+            Backend::Http => Box::new(PlainHttp),
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct FetchArgs {
+    /// The day to fetch
+    pub day: u8,
+
+    /// The event year
+    #[arg(long, default_value_t = 2023)]
+    pub year: u16,
+
+    /// The server to fetch from
+    #[arg(long, default_value = "https://adventofcode.com")]
+    pub base_url: String,
+
+    /// A file holding the session token. Defaults to the AOC_SESSION environment variable
+    #[arg(long)]
+    pub session_file: Option<PathBuf>,
+
+    /// How to download (curl, http)
+    #[arg(long, default_value = "curl")]
+    pub backend: Backend,
+
+    /// The directory inputs are cached in
+    #[arg(long, default_value = "data")]
+    pub data_dir: PathBuf,
+}
+
+/// Replaces the HTML entities the puzzle pages use in code blocks.
+fn unescape_html(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Extracts the first code block of a puzzle page, which holds the example input.
+fn extract_example(page: &str) -> Option<String> {
+    let start = page.find("<pre><code>")? + "<pre><code>".len();
+    let end = start + page[start..].find("</code></pre>")?;
+    let mut example = String::new();
+    let mut in_tag = false;
+    for c in page[start..end].chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => example.push(c),
+            _ => {}
+        }
+    }
+    Some(unescape_html(&example))
+}
+
+/// Downloads the input and example of a day into `data/<day>/`, skipping files that
+/// already exist.
+pub fn fetch(args: &FetchArgs) -> Result<()> {
+    let client = args.backend.client();
+    // The token is never taken as an argument, which would show up in process lists and
+    // shell history.
+    let session = match &args.session_file {
+        Some(file) => Some(
+            fs::read_to_string(file)
+                .context(format!("Failed to read file {:#?}", file))?
+                .trim()
+                .to_owned(),
+        ),
+        None => std::env::var("AOC_SESSION").ok(),
+    };
+    let base_url = args.base_url.trim_end_matches('/');
+    let day_url = format!("{}/{}/day/{}", base_url, args.year, args.day);
+    let dir = args.data_dir.join(args.day.to_string());
+    fs::create_dir_all(&dir).context(format!("Failed to create directory {:#?}", dir))?;
+
+    let input_file = dir.join("input");
+    if input_file.exists() {
+        println!("{} already exists", input_file.display());
+    } else {
+        let session = session.as_deref().ok_or(Error::msg(
+            "Fetching an input needs a session token, from --session-file or AOC_SESSION",
+        ))?;
+        let input = client.get(&format!("{}/input", day_url), Some(session))?;
+        fs::write(&input_file, input).context(format!("Failed to write file {:#?}", input_file))?;
+        println!("Fetched {}", input_file.display());
+    }
+
+    let example_file = dir.join("example");
+    if example_file.exists() {
+        println!("{} already exists", example_file.display());
+    } else {
+        let page = client.get(&day_url, session.as_deref())?;
+        let example =
+            extract_example(&page).ok_or(Error::msg(format!("No example found on {}", day_url)))?;
+        fs::write(&example_file, example)
+            .context(format!("Failed to write file {:#?}", example_file))?;
+        println!("Fetched {}", example_file.display());
+    }
+    Ok(())
+}
@@ -1,16 +1,190 @@
+use anyhow::{Context, Error, Result};
+use memmap2::Mmap;
 use std::{
     fs::File,
-    io::{self, BufRead},
-    path::Path,
+    io::{self, BufRead, Read},
+    path::{Path, PathBuf},
 };
 
-// https://doc.rust-lang.org/rust-by-example/std_misc/file/read_lines.html#a-more-efficient-approach
-// The output is wrapped in a Result to allow matching on errors
-// Returns an Iterator to the Reader of the lines of the file.
-pub fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
+/// How the readers in this module load files and clean up lines before parsers see them.
+/// Byte order marks and carriage returns are always stripped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadOptions {
+    /// Also strip trailing whitespace from every line.
+    pub trim_trailing_whitespace: bool,
+    /// Let [`InputFile`] memory-map regular files. Must be off whenever an input may be
+    /// rewritten while a solver runs, as in watch mode.
+    pub map: bool,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions {
+            trim_trailing_whitespace: false,
+            map: true,
+        }
+    }
+}
+
+/// Strips a UTF-8 byte order mark from the first line, a carriage return left over from
+/// CRLF line endings and, if enabled, trailing whitespace.
+fn normalize(line: &str, line_number: usize, options: ReadOptions) -> &str {
+    let mut line = line;
+    if line_number == 1 {
+        line = line.strip_prefix('\u{feff}').unwrap_or(line);
+    }
+    line = line.strip_suffix('\r').unwrap_or(line);
+    if options.trim_trailing_whitespace {
+        line = line.trim_end();
+    }
+    line
+}
+
+/// Describes a line of an input file, for use as error context.
+pub fn at(file: &Path, line_number: usize) -> String {
+    format!("at {}:{}", file.display(), line_number)
+}
+
+/// Iterates over the normalized lines of a file as `(line_number, line)` pairs, numbered
+/// from 1.
+///
+/// A line that cannot be read or is not valid UTF-8 is returned as an error naming its
+/// position, after which the iterator stops.
+pub struct NumberedLines {
+    path: PathBuf,
+    lines: io::Lines<io::BufReader<File>>,
+    options: ReadOptions,
+    line_number: usize,
+    failed: bool,
+}
+
+impl Iterator for NumberedLines {
+    type Item = Result<(usize, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let line = self.lines.next()?;
+        self.line_number += 1;
+        Some(match line {
+            Ok(line) => {
+                let normalized = normalize(&line, self.line_number, self.options);
+                let line = if normalized.len() == line.len() {
+                    line
+                } else {
+                    normalized.to_owned()
+                };
+                Ok((self.line_number, line))
+            }
+            Err(err) => {
+                self.failed = true;
+                Err(Error::new(err).context(format!(
+                    "Failed to read line {}",
+                    at(&self.path, self.line_number)
+                )))
+            }
+        })
+    }
+}
+
+pub fn read_numbered_lines<P>(filename: P, options: ReadOptions) -> Result<NumberedLines>
 where
     P: AsRef<Path>,
 {
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
+    let path = filename.as_ref().to_owned();
+    let file = File::open(&path).context(format!("Failed to read file {:#?}", path))?;
+    Ok(NumberedLines {
+        path,
+        lines: io::BufReader::new(file).lines(),
+        options,
+        line_number: 0,
+        failed: false,
+    })
+}
+
+/// The bytes of an input file, mapped if possible and read into memory otherwise.
+enum Data {
+    Mapped(Mmap),
+    Read(Vec<u8>),
+}
+
+impl Data {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Data::Mapped(data) => data,
+            Data::Read(data) => data,
+        }
+    }
+}
+
+/// The whole contents of an input file, checked to be valid UTF-8, so that parsers can
+/// borrow from it instead of allocating a `String` per line.
+///
+/// Regular files are memory-mapped unless [`ReadOptions::map`] is off.
+/// Pipes, FIFOs and files that cannot be mapped are read into memory instead.
+pub struct InputFile {
+    path: PathBuf,
+    data: Data,
+    options: ReadOptions,
+}
+
+impl InputFile {
+    pub fn open<P>(filename: P, options: ReadOptions) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = filename.as_ref().to_owned();
+        let mut file = File::open(&path).context(format!("Failed to read file {:#?}", path))?;
+        let regular = file.metadata().is_ok_and(|metadata| metadata.is_file());
+        // SAFETY: The mapping is only sound while no other process modifies the file.
+        // Inputs are only rewritten while a solver runs in watch mode, which turns
+        // `options.map` off.
+        let mapped = (regular && options.map)
+            .then(|| unsafe { Mmap::map(&file) }.ok())
+            .flatten();
+        let data = match mapped {
+            Some(data) => Data::Mapped(data),
+            None => {
+                let mut data = vec![];
+                file.read_to_end(&mut data)
+                    .context(format!("Failed to read file {:#?}", path))?;
+                Data::Read(data)
+            }
+        };
+        if let Err(err) = std::str::from_utf8(data.bytes()) {
+            let line_number = data.bytes()[..err.valid_up_to()]
+                .iter()
+                .filter(|b| **b == b'\n')
+                .count()
+                + 1;
+            return Err(
+                Error::new(err).context(format!("Failed to read line {}", at(&path, line_number)))
+            );
+        }
+        Ok(InputFile {
+            path,
+            data,
+            options,
+        })
+    }
+
+    /// Describes a line of this file, for use as error context.
+    pub fn at(&self, line_number: usize) -> String {
+        at(&self.path, line_number)
+    }
+
+    pub fn text(&self) -> &str {
+        // SAFETY: `open` checked that the data is valid UTF-8, and it is never modified.
+        unsafe { std::str::from_utf8_unchecked(self.data.bytes()) }
+    }
+
+    /// Iterates over the normalized lines of the file as `(line_number, line)` pairs,
+    /// numbered from 1.
+    pub fn lines(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.text()
+            .lines()
+            .enumerate()
+            .map(|(line_idx, line)| (line_idx + 1, normalize(line, line_idx + 1, self.options)))
+    }
 }
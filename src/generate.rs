@@ -0,0 +1,52 @@
+use anyhow::{Error, Result};
+use clap::Args;
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{day1, day2, day3, day4, day5, day6, day7, day8};
+
+#[derive(Args, Debug, Clone)]
+pub struct GeneratorOptions {
+    /// Seed for the random number generator
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Number of lines (day 1), games (2), rows and columns (3), cards (4), races (6),
+    /// hands (7) or nodes (8). Defaults to the size of a typical puzzle input.
+    #[arg(long)]
+    pub count: Option<usize>,
+
+    /// Number of seed ranges for day 5
+    #[arg(long, default_value_t = 10)]
+    pub seed_ranges: usize,
+
+    /// Number of entries per map for day 5
+    #[arg(long, default_value_t = 30)]
+    pub map_entries: usize,
+
+    /// Maximum length of seed ranges and map entries for day 5
+    #[arg(long, default_value_t = 1000)]
+    pub max_len: u64,
+}
+
+/// Generates a random, valid puzzle input for `day`. The same options always produce the
+/// same input.
+pub fn generate_input(day: u8, options: &GeneratorOptions) -> Result<String> {
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let count = |default| options.count.unwrap_or(default);
+    Ok(match day {
+        1 => day1::generate_input(&mut rng, count(1000)),
+        2 => day2::generate_input(&mut rng, count(100)),
+        3 => day3::generate_input(&mut rng, count(140)),
+        4 => day4::generate_input(&mut rng, count(200)),
+        5 => day5::generate_input(
+            &mut rng,
+            options.seed_ranges,
+            options.map_entries,
+            options.max_len,
+        ),
+        6 => day6::generate_input(&mut rng, count(4)),
+        7 => day7::generate_input(&mut rng, count(1000)),
+        8 => day8::generate_input(&mut rng, count(700)),
+        _ => return Err(Error::msg(format!("No input generator for day {}", day))),
+    })
+}
@@ -0,0 +1,207 @@
+use anyhow::{Context, Error, Result};
+use std::path::Path;
+
+use crate::filemanip::at;
+
+#[allow(dead_code)]
+const OFFSETS_4: &[(isize, isize)] = &[(-1, 0), (0, -1), (0, 1), (1, 0)];
+
+const OFFSETS_8: &[(isize, isize)] = &[
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+/// A rectangular grid stored in row-major order. Positions are `(row, col)` pairs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl Grid<char> {
+    /// Builds a grid of characters from numbered lines of equal length, as read from `file`.
+    pub fn from_lines<S: AsRef<str>>(lines: &[(usize, S)], file: &Path) -> Result<Self> {
+        let width = lines
+            .first()
+            .map_or(0, |(_, line)| line.as_ref().chars().count());
+        let mut cells = Vec::with_capacity(width * lines.len());
+        for (line_number, line) in lines {
+            let len_before = cells.len();
+            cells.extend(line.as_ref().chars());
+            if cells.len() - len_before != width {
+                return Err(Error::msg(format!(
+                    "Found {} columns, expected {}",
+                    cells.len() - len_before,
+                    width
+                )))
+                .context(format!("Failed to parse row {}", at(file, *line_number)));
+            }
+        }
+        Ok(Grid {
+            width,
+            height: lines.len(),
+            cells,
+        })
+    }
+}
+
+impl<T> Grid<T> {
+    #[allow(dead_code)]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[allow(dead_code)]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    #[allow(dead_code)]
+    pub fn get(&self, row: usize, col: usize) -> Option<&T> {
+        if row < self.height && col < self.width {
+            self.cells.get(row * self.width + col)
+        } else {
+            None
+        }
+    }
+
+    fn offset(
+        &self,
+        (row, col): (usize, usize),
+        (d_row, d_col): (isize, isize),
+    ) -> Option<(usize, usize)> {
+        let row = row
+            .checked_add_signed(d_row)
+            .filter(|row| *row < self.height)?;
+        let col = col
+            .checked_add_signed(d_col)
+            .filter(|col| *col < self.width)?;
+        Some((row, col))
+    }
+
+    /// The positions directly above, left of, right of and below `(row, col)` that lie
+    /// inside the grid.
+    #[allow(dead_code)]
+    pub fn neighbours4(&self, row: usize, col: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        OFFSETS_4
+            .iter()
+            .filter_map(move |offset| self.offset((row, col), *offset))
+    }
+
+    /// The positions surrounding `(row, col)`, including diagonals, that lie inside the
+    /// grid.
+    pub fn neighbours8(&self, row: usize, col: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        OFFSETS_8
+            .iter()
+            .filter_map(move |offset| self.offset((row, col), *offset))
+    }
+
+    #[allow(dead_code)]
+    pub fn row(&self, row: usize) -> Option<&[T]> {
+        (row < self.height).then(|| &self.cells[row * self.width..(row + 1) * self.width])
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // chunks_exact panics on a chunk size of zero, which an empty grid would have.
+        self.cells.chunks_exact(self.width.max(1))
+    }
+
+    #[allow(dead_code)]
+    pub fn column(&self, col: usize) -> impl Iterator<Item = &T> {
+        self.cells
+            .iter()
+            .skip(col)
+            .step_by(self.width.max(1))
+            .take(if col < self.width { self.height } else { 0 })
+    }
+
+    #[allow(dead_code)]
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = &T>> {
+        (0..self.width).map(|col| self.column(col))
+    }
+
+    /// Iterates over all cells together with their positions, in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), &T)> {
+        self.cells
+            .iter()
+            .enumerate()
+            .map(|(idx, cell)| ((idx / self.width, idx % self.width), cell))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_lines(lines: &[&str]) -> Result<Grid<char>> {
+        let lines = lines
+            .iter()
+            .enumerate()
+            .map(|(idx, line)| (idx + 1, *line))
+            .collect::<Vec<_>>();
+        Grid::from_lines(&lines, Path::new("grid"))
+    }
+
+    fn grid() -> Grid<char> {
+        from_lines(&["abc", "def", "ghi"]).unwrap()
+    }
+
+    #[test]
+    fn corner_neighbours_stay_inside() {
+        let grid = grid();
+        assert_eq!(grid.neighbours4(0, 0).collect::<Vec<_>>(), [(0, 1), (1, 0)]);
+        assert_eq!(
+            grid.neighbours8(2, 2).collect::<Vec<_>>(),
+            [(1, 1), (1, 2), (2, 1)]
+        );
+    }
+
+    #[test]
+    fn edge_neighbours_stay_inside() {
+        let grid = grid();
+        assert_eq!(
+            grid.neighbours4(1, 2).collect::<Vec<_>>(),
+            [(0, 2), (1, 1), (2, 2)]
+        );
+        assert_eq!(
+            grid.neighbours8(0, 1).collect::<Vec<_>>(),
+            [(0, 0), (0, 2), (1, 0), (1, 1), (1, 2)]
+        );
+    }
+
+    #[test]
+    fn empty_grid() {
+        let grid = from_lines(&[]).unwrap();
+        assert_eq!((grid.width(), grid.height()), (0, 0));
+        assert_eq!(grid.rows().count(), 0);
+        assert_eq!(grid.columns().count(), 0);
+        assert_eq!(grid.iter().count(), 0);
+        assert_eq!(grid.get(0, 0), None);
+    }
+
+    #[test]
+    fn rows_and_columns() {
+        let grid = grid();
+        assert_eq!(grid.row(1), Some(&['d', 'e', 'f'][..]));
+        assert_eq!(grid.row(3), None);
+        assert_eq!(grid.column(2).collect::<String>(), "cfi");
+        assert_eq!(grid.column(3).count(), 0);
+        assert_eq!(grid.get(0, 3), None);
+    }
+
+    #[test]
+    fn ragged_rows_are_rejected() {
+        let err = from_lines(&["abc", "abc", "de"]).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "Failed to parse row at grid:3: Found 2 columns, expected 3"
+        );
+    }
+}
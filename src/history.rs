@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::filemanip::{at, read_numbered_lines, ReadOptions};
+
+/// The ledger used when none is given, next to the `data/` directory.
+pub const DEFAULT_LEDGER: &str = "answers.jsonl";
+
+/// One recorded answer. The ledger stores one entry per line as JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub day: u8,
+    pub part: u8,
+    /// The SHA-256 of the input file, in hex.
+    pub input_hash: String,
+    pub answer: u64,
+    /// Seconds since the Unix epoch.
+    pub timestamp: i64,
+    /// The commit checked out in the directory the solver ran in, if that is a git
+    /// checkout. This is not necessarily the commit the solver was built from.
+    pub commit: Option<String>,
+}
+
+impl Entry {
+    /// When the answer was recorded, in RFC 3339 format.
+    pub fn time(&self) -> String {
+        OffsetDateTime::from_unix_timestamp(self.timestamp)
+            .ok()
+            .and_then(|time| time.format(&Rfc3339).ok())
+            .unwrap_or_else(|| self.timestamp.to_string())
+    }
+}
+
+pub fn hash_input(file: &Path) -> Result<String> {
+    let data = fs::read(file).context(format!("Failed to read file {:#?}", file))?;
+    Ok(format!("{:x}", Sha256::digest(data)))
+}
+
+fn current_commit() -> Option<String> {
+    let output = process::Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_owned())
+}
+
+/// Reads all entries of the ledger. A missing ledger has no entries.
+pub fn read_ledger(ledger: &Path) -> Result<Vec<Entry>> {
+    if !ledger.exists() {
+        return Ok(vec![]);
+    }
+    let mut entries = vec![];
+    for line in read_numbered_lines(ledger, ReadOptions::default())? {
+        let (line_number, line) = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(
+            serde_json::from_str(&line)
+                .context(format!("Failed to parse entry {}", at(ledger, line_number)))?,
+        );
+    }
+    Ok(entries)
+}
+
+/// Appends an answer to the ledger. Returns the last entry recorded for the same day, part
+/// and input if it had a different answer.
+pub fn record(
+    ledger: &Path,
+    day: u8,
+    part: u8,
+    input: &Path,
+    answer: u64,
+) -> Result<Option<Entry>> {
+    let input_hash = hash_input(input)?;
+    let previous = read_ledger(ledger)?
+        .into_iter()
+        .rev()
+        .find(|entry| entry.day == day && entry.part == part && entry.input_hash == input_hash);
+    let entry = Entry {
+        day,
+        part,
+        input_hash,
+        answer,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs() as i64),
+        commit: current_commit(),
+    };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(ledger)
+        .context(format!("Failed to open ledger {:#?}", ledger))?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)
+        .context(format!("Failed to write ledger {:#?}", ledger))?;
+    Ok(previous.filter(|previous| previous.answer != answer))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    pub entries: Vec<Entry>,
+}
+
+impl fmt::Display for History {
+    /// Lists the entries oldest first, marking answers that differ from the previous one
+    /// for the same part and input with `*`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<25} {:>4} {:>16}  {:<12} commit",
+            "time", "part", "answer", "input"
+        )?;
+        for (idx, entry) in self.entries.iter().enumerate() {
+            let changed = self.entries[..idx]
+                .iter()
+                .rev()
+                .find(|other| other.part == entry.part && other.input_hash == entry.input_hash)
+                .is_some_and(|other| other.answer != entry.answer);
+            writeln!(
+                f,
+                "{:<25} {:>4} {:>16}{} {:<12} {}",
+                entry.time(),
+                entry.part,
+                entry.answer,
+                if changed { "*" } else { " " },
+                &entry.input_hash[..entry.input_hash.len().min(12)],
+                entry.commit.as_deref().unwrap_or("-")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The recorded answers for `day`, optionally only those for one part.
+pub fn history(ledger: &Path, day: u8, part: Option<u8>) -> Result<History> {
+    let entries = read_ledger(ledger)?
+        .into_iter()
+        .filter(|entry| entry.day == day && part.is_none_or(|part| entry.part == part))
+        .collect();
+    Ok(History { entries })
+}
@@ -1,6 +1,7 @@
 use anyhow::anyhow;
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+use std::io::IsTerminal;
 
 mod day1;
 mod day2;
@@ -10,10 +11,95 @@ mod day5;
 mod day6;
 mod day7;
 mod day8;
+mod difftest;
+mod fetch;
 mod filemanip;
+mod generate;
+mod grid;
+mod history;
+mod parse;
+mod progress;
+mod scaffold;
+mod watch;
 
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    run: Option<RunArgs>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a random puzzle input
+    Generate {
+        /// The day to generate an input for
+        day: u8,
+
+        /// Write the input to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<std::path::PathBuf>,
+
+        #[command(flatten)]
+        options: generate::GeneratorOptions,
+    },
+
+    /// Print the structured form of a puzzle input instead of solving it
+    Parse {
+        /// The day of the input
+        day: u8,
+
+        /// The input file
+        input: std::path::PathBuf,
+
+        /// The output format (json)
+        #[arg(long, default_value = "json")]
+        emit: parse::Emit,
+    },
+
+    /// Download the input and example of a day into the data directory
+    Fetch(fetch::FetchArgs),
+
+    /// Create the module, registration and data files for a new day
+    New {
+        /// The day to create
+        day: u8,
+    },
+
+    /// Re-run a solver whenever its input or source file changes
+    Watch {
+        #[command(flatten)]
+        args: RunArgs,
+
+        /// How often to check for changes, in milliseconds
+        #[arg(long, default_value_t = 500)]
+        interval: u64,
+    },
+
+    /// Show the answers recorded for a day
+    History {
+        /// The day to show the answers of
+        day: u8,
+
+        /// Only show the answers of this challenge
+        #[arg(long)]
+        challenge: Option<u8>,
+
+        /// The ledger answers are recorded in
+        #[arg(long, default_value = history::DEFAULT_LEDGER)]
+        ledger: std::path::PathBuf,
+    },
+
+    /// Compare two implementations of a challenge and report the first input they
+    /// disagree on
+    Difftest(difftest::DifftestArgs),
+}
+
+#[derive(Args)]
+struct RunArgs {
     /// The day of the challenge
     day: u8,
 
@@ -22,63 +108,224 @@ struct Cli {
 
     /// The input file
     input: std::path::PathBuf,
+
+    /// Languages whose spelled-out digits count in day 1 challenge 2 (en, de, fr)
+    #[arg(long = "locale", default_value = "en")]
+    locales: Vec<day1::Locale>,
+
+    /// Also count the spelled-out word for zero in day 1 challenge 2
+    #[arg(long)]
+    zero: bool,
+
+    /// Additional word list for day 1 challenge 2, one "<word> <value>" pair per line
+    #[arg(long)]
+    words: Option<std::path::PathBuf>,
+
+    /// The bag contents to check day 2 challenge 1 games against
+    #[arg(long, default_value = "12 red, 13 green, 14 blue")]
+    bag: day2::Cubes,
+
+    /// Sum duplicate colors and skip empty rounds in day 2 instead of rejecting them
+    #[arg(long)]
+    lenient: bool,
+
+    /// Only sum the numbers touching this symbol in day 3 challenge 1
+    #[arg(long)]
+    touching: Option<char>,
+
+    /// Symbols that count as gears in day 3 challenge 2
+    #[arg(long, default_value = "*")]
+    gear_symbols: String,
+
+    /// Number of adjacent numbers a gear needs in day 3 challenge 2, as N or MIN-MAX
+    #[arg(long, default_value = "2", value_parser = day3::parse_neighbour_count)]
+    gear_neighbours: std::ops::RangeInclusive<usize>,
+
+    /// How the numbers around a gear are combined in day 3 challenge 2 (product, sum)
+    #[arg(long, default_value = "product")]
+    gear_combine: day3::Combine,
+
+    /// Strip trailing whitespace from every input line
+    #[arg(long)]
+    trim_trailing_whitespace: bool,
+
+    /// Print a detailed report instead of only the answer
+    #[arg(long)]
+    report: bool,
+
+    /// Skip input lines without a value instead of aborting
+    #[arg(long)]
+    skip_invalid: bool,
+
+    /// The ledger answers are recorded in
+    #[arg(long, default_value = history::DEFAULT_LEDGER)]
+    ledger: std::path::PathBuf,
+
+    /// Do not record the answer in the ledger
+    #[arg(long)]
+    no_history: bool,
+
+    /// Abort solvers that report progress (days 5 and 8) after this many seconds
+    #[arg(long, value_parser = progress::parse_timeout)]
+    timeout: Option<std::time::Duration>,
 }
 
-fn main() -> Result<()> {
-    let args = Cli::parse();
+fn day1_vocabulary(args: &RunArgs) -> Result<day1::Vocabulary> {
+    let mut vocabulary = day1::Vocabulary::default();
+    if args.challenge == 1 {
+        return Ok(vocabulary);
+    }
+    for locale in &args.locales {
+        vocabulary.extend(day1::Vocabulary::from_locale(*locale, args.zero));
+    }
+    if let Some(words) = &args.words {
+        vocabulary.extend(day1::Vocabulary::from_file(words)?);
+    }
+    Ok(vocabulary)
+}
 
-    match (args.day, args.challenge) {
-        (1, 1) => {
-            println!("{:#?}", day1::calculate_calibration_sum(&args.input, false))
-        }
-        (1, 2) => {
-            println!("{:#?}", day1::calculate_calibration_sum(&args.input, true))
-        }
-        (2, 1) => {
-            println!("{:#?}", day2::calculate_valid_game_sum(&args.input))
-        }
-        (2, 2) => {
-            println!("{:#?}", day2::calculate_game_power_sum(&args.input))
-        }
-        (3, 1) => {
-            println!("{:#?}", day3::calculate_schematic_sum(&args.input))
-        }
-        (3, 2) => {
-            println!("{:#?}", day3::calculate_gear_ratio_sum(&args.input))
-        }
-        (4, 1) => {
-            println!("{:#?}", day4::calculate_scratchcard_sum(&args.input))
-        }
-        (4, 2) => {
-            println!("{:#?}", day4::calculate_scratchcard_count(&args.input))
-        }
-        (5, 1) => {
-            println!("{:#?}", day5::calculate_lowest_location_number(&args.input))
-        }
-        (5, 2) => {
-            println!(
-                "{:#?}",
-                day5::calculate_lowest_location_number_range(&args.input)
-            )
+fn read_options(args: &RunArgs) -> filemanip::ReadOptions {
+    filemanip::ReadOptions {
+        trim_trailing_whitespace: args.trim_trailing_whitespace,
+        ..Default::default()
+    }
+}
+
+fn day3_gear_rule(args: &RunArgs) -> day3::GearRule {
+    day3::GearRule {
+        symbols: args.gear_symbols.chars().collect(),
+        neighbours: args.gear_neighbours.clone(),
+        combine: args.gear_combine,
+    }
+}
+
+fn day2_parse_mode(args: &RunArgs) -> day2::ParseMode {
+    if args.lenient {
+        day2::ParseMode::Lenient
+    } else {
+        day2::ParseMode::Strict
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    match (cli.command, cli.run) {
+        (
+            Some(Command::Generate {
+                day,
+                output,
+                options,
+            }),
+            _,
+        ) => {
+            let input = generate::generate_input(day, &options)?;
+            match output {
+                Some(output) => std::fs::write(&output, input)
+                    .context(format!("Failed to write file {:#?}", output))?,
+                None => print!("{}", input),
+            }
+            Ok(())
         }
-        (6, 1) => {
-            println!("{:#?}", day6::calculate_margin_of_error(&args.input, false))
+        (Some(Command::Parse { day, input, emit }), _) => {
+            println!("{}", parse::parse_input(day, &input, emit)?);
+            Ok(())
         }
-        (6, 2) => {
-            println!("{:#?}", day6::calculate_margin_of_error(&args.input, true))
+        (
+            Some(Command::History {
+                day,
+                challenge,
+                ledger,
+            }),
+            _,
+        ) => {
+            print!("{}", history::history(&ledger, day, challenge)?);
+            Ok(())
         }
-        (7, 1) => {
-            println!("{:#?}", day7::calculate_winning_hands(&args.input, false))
+        (Some(Command::Watch { args, interval }), _) => {
+            watch::watch(&args, std::time::Duration::from_millis(interval))
         }
-        (7, 2) => {
-            println!("{:#?}", day7::calculate_winning_hands(&args.input, true))
+        (Some(Command::New { day }), _) => scaffold::new_day(day),
+        (Some(Command::Fetch(args)), _) => fetch::fetch(&args),
+        (Some(Command::Difftest(args)), _) => difftest::run_difftest(&args),
+        (None, Some(args)) => run(&args),
+        (None, None) => unreachable!("clap requires either a subcommand or the run arguments"),
+    }
+}
+
+/// Renders the detailed report for the challenge, if it has one.
+fn report(args: &RunArgs) -> Result<Option<String>> {
+    let options = read_options(args);
+    Ok(Some(match (args.day, args.challenge) {
+        (1, 1 | 2) => {
+            day1::calibration_report(&args.input, options, &day1_vocabulary(args)?)?.to_string()
         }
-        (8, 1) => {
-            println!("{:#?}", day8::calculate_zzz_steps(&args.input))
+        (2, 1) => day2::validity_report(&args.input, options, &args.bag, day2_parse_mode(args))?
+            .to_string(),
+        (3, 1 | 2) => day3::render_schematic(
+            &day3::Schematic::from_file(&args.input, options)?,
+            &day3_gear_rule(args),
+            std::io::stdout().is_terminal(),
+        ),
+        (4, 1 | 2) => day4::scratchcard_report(&args.input, options)?.to_string(),
+        _ => return Ok(None),
+    }))
+}
+
+/// Runs the solver for the challenge. The outer error means the challenge could not be set
+/// up, the inner one is the solver's own result.
+fn solve(args: &RunArgs, options: filemanip::ReadOptions) -> Result<Result<u64>> {
+    let _run = progress::start(args.timeout, std::io::stderr().is_terminal());
+    let answer = match (args.day, args.challenge) {
+        (1, 1 | 2) => day1::calculate_calibration_sum(
+            &args.input,
+            options,
+            &day1_vocabulary(args)?,
+            args.skip_invalid,
+        ),
+        (2, 1) => {
+            day2::calculate_valid_game_sum(&args.input, options, &args.bag, day2_parse_mode(args))
         }
+        (2, 2) => day2::calculate_game_power_sum(&args.input, options, day2_parse_mode(args)),
+        (3, 1) => day3::calculate_schematic_sum(&args.input, options, args.touching),
+        (3, 2) => day3::calculate_gear_ratio_sum(&args.input, options, &day3_gear_rule(args)),
+        (4, 1) => day4::calculate_scratchcard_sum(&args.input, options),
+        (4, 2) => day4::calculate_scratchcard_count(&args.input, options),
+        (5, 1) => day5::calculate_lowest_location_number(&args.input, options),
+        (5, 2) => day5::calculate_lowest_location_number_range(&args.input, options),
+        (6, 1) => day6::calculate_margin_of_error(&args.input, options, false),
+        (6, 2) => day6::calculate_margin_of_error(&args.input, options, true),
+        (7, 1) => day7::calculate_winning_hands(&args.input, options, false),
+        (7, 2) => day7::calculate_winning_hands(&args.input, options, true),
+        (8, 1) => day8::calculate_zzz_steps(&args.input, options),
         (d, c) => {
             return Err(anyhow!("Invalid day / challenge combination: {} {}!", d, c));
         }
+    };
+    Ok(answer)
+}
+
+fn run(args: &RunArgs) -> Result<()> {
+    if args.report {
+        if let Some(report) = report(args)? {
+            print!("{}", report);
+            return Ok(());
+        }
+    }
+    let answer = solve(args, read_options(args))?;
+    println!("{:#?}", answer);
+
+    // The answer is already printed, so failing to record it is not worth failing the run.
+    if let (Ok(answer), false) = (answer, args.no_history) {
+        match history::record(&args.ledger, args.day, args.challenge, &args.input, answer) {
+            Ok(Some(previous)) => eprintln!(
+                "Warning: the answer for this input changed from {} (recorded {}) to {}",
+                previous.answer,
+                previous.time(),
+                answer
+            ),
+            Ok(None) => {}
+            Err(err) => eprintln!("Warning: failed to record the answer: {:#}", err),
+        }
     }
     Ok(())
 }
@@ -0,0 +1,49 @@
+use anyhow::{Error, Result};
+use std::{path::Path, str::FromStr};
+
+use crate::{day1, day2, day3, day4, day5, day6, day7, day8, filemanip::ReadOptions};
+
+/// The formats a parsed input can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    Json,
+}
+
+impl FromStr for Emit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Emit::Json),
+            _ => Err(Error::msg(format!("Unknown output format {:?}", s))),
+        }
+    }
+}
+
+/// Parses the input for `day` and writes its structured form instead of solving it.
+///
+/// Day 1 lines are matched against the English digit words, and day 6 races are read
+/// without kerning.
+pub fn parse_input(day: u8, file: &Path, emit: Emit) -> Result<String> {
+    let options = ReadOptions::default();
+    let json = match (day, emit) {
+        (1, Emit::Json) => serde_json::to_string_pretty(&day1::calibration_report(
+            file,
+            options,
+            &day1::Vocabulary::from_locale(day1::Locale::English, false),
+        )?),
+        (2, Emit::Json) => {
+            serde_json::to_string_pretty(&day2::read_games(file, options, day2::ParseMode::Strict)?)
+        }
+        (3, Emit::Json) => {
+            serde_json::to_string_pretty(&day3::Schematic::from_file(file, options)?)
+        }
+        (4, Emit::Json) => serde_json::to_string_pretty(&day4::read_scratchcards(file, options)?),
+        (5, Emit::Json) => serde_json::to_string_pretty(&day5::read_almanac(file, options)?),
+        (6, Emit::Json) => serde_json::to_string_pretty(&day6::read_races(file, options, false)?),
+        (7, Emit::Json) => serde_json::to_string_pretty(&day7::read_plays(file, options)?),
+        (8, Emit::Json) => serde_json::to_string_pretty(&day8::read_network(file, options)?),
+        _ => return Err(Error::msg(format!("No parser for day {}", day))),
+    }?;
+    Ok(json)
+}
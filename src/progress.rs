@@ -0,0 +1,86 @@
+use anyhow::{Error, Result};
+use std::{
+    io::Write,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// How often the progress line is redrawn at most.
+const RENDER_INTERVAL: Duration = Duration::from_millis(100);
+
+struct State {
+    start: Instant,
+    deadline: Option<Instant>,
+    render: bool,
+    last_render: Option<Instant>,
+}
+
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+/// Parses a timeout given in (fractional) seconds.
+pub fn parse_timeout(s: &str) -> Result<Duration> {
+    Ok(Duration::try_from_secs_f64(s.parse()?)?)
+}
+
+/// Tracks a solver run until it is dropped, which clears the progress line. Dropping it
+/// also covers runs that return early or panic.
+#[must_use = "the run stops being tracked when this is dropped"]
+pub struct Run;
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        if let Some(State {
+            last_render: Some(_),
+            ..
+        }) = STATE.lock().unwrap().take()
+        {
+            eprint!("\r\x1b[2K");
+        }
+    }
+}
+
+/// Starts tracking a solver run. Until this is called, [`report`] does nothing.
+pub fn start(timeout: Option<Duration>, render: bool) -> Run {
+    let start = Instant::now();
+    *STATE.lock().unwrap() = Some(State {
+        start,
+        deadline: timeout.map(|timeout| start + timeout),
+        render,
+        last_render: None,
+    });
+    Run
+}
+
+/// Called by long-running solvers every now and then with the number of items processed
+/// so far and the best answer found so far. Returns an error once the run's timeout has
+/// passed, which the solver should pass on to abort.
+pub fn report(processed: u64, best: Option<u64>) -> Result<()> {
+    let mut state = STATE.lock().unwrap();
+    let Some(state) = state.as_mut() else {
+        return Ok(());
+    };
+    let now = Instant::now();
+    if let Some(deadline) = state.deadline.filter(|deadline| now >= *deadline) {
+        return Err(Error::msg(format!(
+            "Timed out after {:.2?} with {} items processed",
+            deadline - state.start,
+            processed
+        )));
+    }
+    if state.render
+        && state
+            .last_render
+            .is_none_or(|last_render| now - last_render >= RENDER_INTERVAL)
+    {
+        state.last_render = Some(now);
+        let best = best.map_or("-".to_owned(), |best| best.to_string());
+        eprint!(
+            "\r\x1b[2K{} processed, best so far {}, {:.1?} elapsed",
+            processed,
+            best,
+            now - state.start
+        );
+        let _ = std::io::stderr().flush();
+    }
+    Ok(())
+}
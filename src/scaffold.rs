@@ -0,0 +1,112 @@
+use anyhow::{Context, Error, Result};
+use std::{fs, path::Path};
+
+const TEMPLATE: &str = r#"use anyhow::Result;
+
+use crate::filemanip::{read_numbered_lines, ReadOptions};
+
+/// Reads the puzzle input.
+fn parse_input(file: &std::path::Path, options: ReadOptions) -> Result<Vec<String>> {
+    read_numbered_lines(file, options)?
+        .map(|line| line.map(|(_, line)| line))
+        .collect()
+}
+
+pub fn calculate_part_one(file: &std::path::Path, options: ReadOptions) -> Result<u64> {
+    let _input = parse_input(file, options)?;
+    todo!("day {day} part 1")
+}
+
+pub fn calculate_part_two(file: &std::path::Path, options: ReadOptions) -> Result<u64> {
+    let _input = parse_input(file, options)?;
+    todo!("day {day} part 2")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn part_one_example() {
+        let answer = calculate_part_one(
+            std::path::Path::new("data/{day}/example"),
+            ReadOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(answer, 0, "fill in the expected answer for the example");
+    }
+}
+"#;
+
+/// Inserts `text` into `source` before the line starting with `marker`.
+fn insert_before(source: &str, marker: &str, text: &str) -> Result<String> {
+    let position = source
+        .find(&format!("\n{}", marker))
+        .ok_or(Error::msg(format!("Marker {:?} not found", marker.trim())))?
+        + 1;
+    Ok(format!(
+        "{}{}{}",
+        &source[..position],
+        text,
+        &source[position..]
+    ))
+}
+
+/// Registers the module for `day` in `main.rs` and adds match arms for both parts.
+fn register(main: &str, day: u8) -> Result<String> {
+    let last_day_mod = main
+        .lines()
+        .rfind(|line| {
+            line.strip_prefix("mod day")
+                .and_then(|rest| rest.strip_suffix(';'))
+                .is_some_and(|number| number.parse::<u8>().is_ok())
+        })
+        .ok_or(Error::msg("No day modules found"))?;
+    let position = main.find(last_day_mod).expect("line is from main") + last_day_mod.len() + 1;
+    let main = format!(
+        "{}mod day{};\n{}",
+        &main[..position],
+        day,
+        &main[position..]
+    );
+    insert_before(
+        &main,
+        "        (d, c) => {",
+        &format!(
+            "        ({day}, 1) => day{day}::calculate_part_one(&args.input, options),\n        ({day}, 2) => day{day}::calculate_part_two(&args.input, options),\n",
+            day = day
+        ),
+    )
+}
+
+/// Creates `src/day<N>.rs` from a template, registers it in `src/main.rs` and creates
+/// empty example and input files under `data/<N>/`. Existing files are never overwritten.
+pub fn new_day(day: u8) -> Result<()> {
+    let module = Path::new("src").join(format!("day{}.rs", day));
+    if module.exists() {
+        return Err(Error::msg(format!("{} already exists", module.display())));
+    }
+    let main_file = Path::new("src").join("main.rs");
+    let main =
+        fs::read_to_string(&main_file).context(format!("Failed to read file {:#?}", main_file))?;
+    let main = register(&main, day).context(format!("Failed to register day {}", day))?;
+
+    fs::write(&module, TEMPLATE.replace("{day}", &day.to_string()))
+        .context(format!("Failed to write file {:#?}", module))?;
+    println!("Created {}", module.display());
+    fs::write(&main_file, main).context(format!("Failed to write file {:#?}", main_file))?;
+    println!("Registered day {} in {}", day, main_file.display());
+
+    let data_dir = Path::new("data").join(day.to_string());
+    fs::create_dir_all(&data_dir).context(format!("Failed to create directory {:#?}", data_dir))?;
+    for name in ["example", "input"] {
+        let file = data_dir.join(name);
+        if file.exists() {
+            println!("{} already exists", file.display());
+            continue;
+        }
+        fs::write(&file, "").context(format!("Failed to write file {:#?}", file))?;
+        println!("Created {}", file.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use anyhow::{Context, Result};
+use std::{
+    fmt::Write,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{filemanip::ReadOptions, read_options, solve, RunArgs};
+
+fn modified(file: &Path) -> Option<SystemTime> {
+    fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Carries the last outcome over to the restarted solver, so that its first answer is still
+/// compared to it.
+const PREVIOUS_OUTCOME_VAR: &str = "ADVENT_OF_CODE_WATCH_PREVIOUS";
+
+/// The answer of one run and how long it took.
+struct Outcome {
+    answer: String,
+    elapsed: Duration,
+}
+
+impl Outcome {
+    /// Encodes the outcome as the elapsed nanoseconds followed by the answer.
+    fn encode(&self) -> String {
+        format!("{} {}", self.elapsed.as_nanos(), self.answer)
+    }
+
+    fn decode(s: &str) -> Option<Self> {
+        let (elapsed, answer) = s.split_once(' ')?;
+        Some(Outcome {
+            answer: answer.to_owned(),
+            elapsed: Duration::from_nanos(elapsed.parse().ok()?),
+        })
+    }
+}
+
+fn run_once(args: &RunArgs) -> Result<Outcome> {
+    // Saving the input truncates it, which would crash a solver reading it through a mapping.
+    let options = ReadOptions {
+        map: false,
+        ..read_options(args)
+    };
+    let start = Instant::now();
+    let answer = match panic::catch_unwind(AssertUnwindSafe(|| solve(args, options))) {
+        Ok(answer) => match answer? {
+            Ok(answer) => answer.to_string(),
+            Err(err) => format!("error: {:#}", err),
+        },
+        Err(_) => "panicked".to_owned(),
+    };
+    Ok(Outcome {
+        answer,
+        elapsed: start.elapsed(),
+    })
+}
+
+/// Rebuilds the solver and replaces this process with the new binary, keeping the
+/// arguments and passing on the `previous` outcome. Only returns if the build or the
+/// restart failed.
+fn rebuild_and_restart(previous: Option<&Outcome>) -> Result<()> {
+    let exe = std::env::current_exe().context("Failed to locate the running binary")?;
+    let mut build = Command::new("cargo");
+    build.arg("build");
+    if exe
+        .components()
+        .any(|component| component.as_os_str() == "release")
+    {
+        build.arg("--release");
+    }
+    let status = build.status().context("Failed to run cargo build")?;
+    if !status.success() {
+        return Err(anyhow::Error::msg("Build failed, keeping the old solver"));
+    }
+    let mut restart = Command::new(exe);
+    restart.args(std::env::args_os().skip(1));
+    if let Some(previous) = previous {
+        restart.env(PREVIOUS_OUTCOME_VAR, previous.encode());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        Err(anyhow::Error::new(restart.exec()).context("Failed to restart the solver"))
+    }
+    #[cfg(not(unix))]
+    {
+        let status = restart.status().context("Failed to restart the solver")?;
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
+
+/// Runs the solver whenever the input file or the day's source file changes, printing each
+/// answer and its time next to the previous ones. Input changes are picked up by the
+/// running binary, source changes rebuild and restart it.
+pub fn watch(args: &RunArgs, interval: Duration) -> Result<()> {
+    let source = PathBuf::from(format!("src/day{}.rs", args.day));
+    let mut source_modified = modified(&source);
+    println!(
+        "Watching {} and {}, press Ctrl-C to stop",
+        args.input.display(),
+        source.display()
+    );
+
+    let mut input_modified = None;
+    let mut previous = std::env::var(PREVIOUS_OUTCOME_VAR)
+        .ok()
+        .and_then(|previous| Outcome::decode(&previous));
+    loop {
+        if modified(&source) != source_modified {
+            println!("{} changed, rebuilding", source.display());
+            if let Err(err) = rebuild_and_restart(previous.as_ref()) {
+                println!("{:#}", err);
+            }
+            // The restart failed, so only try again once the source changes again.
+            source_modified = modified(&source);
+        }
+        let current = modified(&args.input);
+        if current.is_some() && current != input_modified {
+            input_modified = current;
+            let outcome = run_once(args)?;
+            let mut line = format!("{} in {:.2?}", outcome.answer, outcome.elapsed);
+            if let Some(previous) = &previous {
+                write!(
+                    line,
+                    " (previously {} in {:.2?})",
+                    previous.answer, previous.elapsed
+                )?;
+            }
+            println!("{}", line);
+            previous = Some(outcome);
+        }
+        thread::sleep(interval);
+    }
+}